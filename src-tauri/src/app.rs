@@ -0,0 +1,211 @@
+// No Visitors - 应用生命周期模块
+// 窗口关闭时的清仓同步（自动提交 + 推送）。桌面端和 Android 共用同一入口 run()，
+// 关闭逻辑只在这里维护一份。
+
+use crate::{commands, events, git, locks};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Window, WindowEvent};
+
+// 防止窗口关闭逻辑重复执行的标志
+static IS_CLOSING: AtomicBool = AtomicBool::new(false);
+
+/// 窗口关闭时执行清仓同步（推送本地提交到远程）
+pub async fn on_window_close(app: AppHandle, label: &str) {
+    tracing::info!("[窗口关闭] 窗口 '{}' 正在关闭，开始清仓同步检查", label);
+    
+    use std::path::PathBuf;
+    
+    // 获取工作区路径
+    let workspace_path = match commands::get_workspace_path(app.clone()) {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::warn!("[窗口关闭] 无法获取工作区路径: {}", e);
+            return;
+        }
+    };
+    
+    tracing::info!("[窗口关闭] 工作区路径: {}", workspace_path);
+    
+    // 读取用户配置的远程与分支（分支未配置时自动检测默认分支）
+    let config = commands::read_workspace_config_or_default(&app, "窗口关闭").await;
+    if !config.commit_on_close && !config.sync_on_close {
+        tracing::info!("[窗口关闭] 已关闭自动提交和同步，直接退出");
+        return;
+    }
+    let remote_name = config.remote_name.clone();
+    let branch_name = match config.branch_name.clone() {
+        Some(branch) => branch,
+        None => git::detect_default_branch(&PathBuf::from(&workspace_path), &remote_name)
+            .unwrap_or_else(|_| "main".to_string()),
+    };
+    tracing::info!("[窗口关闭] 同步目标: {}/{}", remote_name, branch_name);
+    
+    // 关闭同步时不访问网络，只做本地提交；没有远程或 PAT 时同样只跳过推送，本地提交照常进行
+    let pat_token = if config.sync_on_close {
+        close_push_token(&app, &workspace_path, &remote_name)
+    } else {
+        tracing::info!("[窗口关闭] 已关闭退出时同步，跳过推送");
+        None
+    };
+    
+    let repo_path = PathBuf::from(&workspace_path);
+    let timeout = Duration::from_secs(config.close_sync_timeout_secs.max(1));
+    
+    // Git 操作是阻塞调用，放到阻塞线程池中执行，这样外层的超时才能生效；
+    // 等待仓库锁（可能有进行中的同步）也计入超时。超时后任务被放弃，进程随后退出
+    let close_app = app.clone();
+    let git_task = async move {
+        // 持有仓库锁，避免与进行中的同步/自动提交并发修改 .git
+        let repo_lock = locks::lock_repo().await;
+        locks::spawn_blocking(move || {
+            let _repo_lock = repo_lock;
+            close_commit_and_push(
+                &close_app,
+                &repo_path,
+                &remote_name,
+                &branch_name,
+                pat_token.as_deref(),
+                &config,
+            );
+        })
+        .await
+    };
+    
+    match tokio::time::timeout(timeout, git_task).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            tracing::warn!("[窗口关闭] ⚠️ 清仓同步任务异常退出: {}", e);
+        }
+        Err(_) => {
+            tracing::warn!(
+                "[窗口关闭] ⚠️ 清仓同步超过 {} 秒未完成，放弃同步并退出",
+                timeout.as_secs()
+            );
+        }
+    }
+}
+
+/// 获取退出时推送使用的 PAT Token（未单独配置时使用全局令牌）；未配置远程或令牌时返回 None，跳过推送
+fn close_push_token(app: &AppHandle, workspace_path: &str, remote_name: &str) -> Option<String> {
+    let remote_url = match commands::get_remote_url(workspace_path.to_string(), remote_name.to_string()) {
+        Ok(Some(url)) => {
+            tracing::info!("[窗口关闭] 远程 URL: {}", crate::logging::redact_secrets(&url));
+            url
+        }
+        Ok(None) => {
+            tracing::info!("[窗口关闭] 未配置远程仓库，跳过推送");
+            return None;
+        }
+        Err(e) => {
+            tracing::warn!("[窗口关闭] 获取远程 URL 失败，跳过推送: {}", e);
+            return None;
+        }
+    };
+
+    match crate::keychain::get_pat_for(app, &remote_url) {
+        Ok(Some(token)) => {
+            tracing::info!("[窗口关闭] PAT Token 已配置");
+            Some(token)
+        }
+        Ok(None) => {
+            tracing::info!("[窗口关闭] 未配置 PAT Token，跳过推送");
+            None
+        }
+        Err(e) => {
+            tracing::warn!("[窗口关闭] 获取 PAT Token 失败，跳过推送: {}", e);
+            None
+        }
+    }
+}
+
+/// 窗口关闭时的提交与推送（阻塞执行）
+/// 
+/// `pat_token` 为 None 时（关闭了退出时同步，或没有远程/令牌）只做本地提交
+fn close_commit_and_push(
+    app: &AppHandle,
+    repo_path: &std::path::Path,
+    remote_name: &str,
+    branch_name: &str,
+    pat_token: Option<&str>,
+    config: &commands::WorkspaceConfig,
+) {
+    // 检查是否有未提交的更改，如果有则先提交
+    match git::get_repository_status(repo_path) {
+        Ok(status) => {
+            if !config.commit_on_close {
+                tracing::info!("[窗口关闭] 已关闭退出时自动提交，跳过提交");
+            } else if status.has_changes {
+                tracing::info!("[窗口关闭] 检测到未提交的更改，先自动提交...");
+                // 按工作区配置的模板生成提交消息
+                let commit_message = git::render_commit_message(&config.commit_message_template, status.changed_files, branch_name);
+                // 状态中的更改可能只是 stat 信息变化，树与 HEAD 相同时不会创建空提交
+                match git::commit_changes_if_changed(repo_path, &commit_message, branch_name) {
+                    Ok(commit) if commit.created => {
+                        tracing::info!("[窗口关闭] ✅ 自动提交成功: {}", commit.sha);
+                        events::emit_commit_done(app, &commit.sha, &commit_message);
+                    }
+                    Ok(_) => {
+                        tracing::info!("[窗口关闭] 文件内容与 HEAD 相同，无需提交");
+                    }
+                    Err(e) => {
+                        tracing::warn!("[窗口关闭] ⚠️ 自动提交失败: {}", e);
+                        // 即使提交失败，也尝试推送已有的提交
+                    }
+                }
+            } else {
+                tracing::info!("[窗口关闭] 工作区干净，无需提交");
+            }
+            
+            let Some(pat_token) = pat_token else {
+                return;
+            };
+            
+            // 无论是否有未提交的更改，都尝试推送本地提交
+            tracing::info!("[窗口关闭] 尝试推送本地提交到远程...");
+            match git::push_to_remote(repo_path, remote_name, branch_name, Some(pat_token)) {
+                Ok(_) => {
+                    tracing::info!("[窗口关闭] ✅ 推送成功");
+                    events::emit_push_done(app, remote_name, branch_name);
+                }
+                Err(e) => {
+                    // 如果是因为已经是最新的而失败，这是正常的
+                    let error_msg = e.to_string();
+                    if error_msg.contains("already up to date") || error_msg.contains("Everything up-to-date") {
+                        tracing::info!("[窗口关闭] ℹ️ 本地已是最新，无需推送");
+                    } else {
+                        tracing::warn!("[窗口关闭] ⚠️ 推送失败（不影响应用关闭）: {}", e);
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            tracing::warn!("[窗口关闭] ⚠️ 无法获取仓库状态: {}", e);
+        }
+    }
+}
+
+/// 窗口事件处理：拦截关闭请求，完成清仓同步后再退出应用
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if let WindowEvent::CloseRequested { api, .. } = event {
+        // Check if already in closing process
+        if IS_CLOSING.load(Ordering::Acquire) {
+            tracing::info!("[窗口关闭] 已在关闭流程中，直接关闭窗口");
+            return; // Allow direct close if already in process
+        }
+        
+        tracing::info!("[窗口关闭] 检测到窗口关闭请求");
+        IS_CLOSING.store(true, Ordering::Release); // Set flag
+        
+        api.prevent_close(); // Prevent immediate close
+        
+        let app_handle = window.app_handle().clone();
+        let window_label = window.label().to_string();
+        
+        tauri::async_runtime::spawn(async move {
+            on_window_close(app_handle.clone(), &window_label).await;
+            tracing::info!("[窗口关闭] 同步完成，准备关闭窗口");
+            app_handle.exit(0); // Exit application
+        });
+    }
+}
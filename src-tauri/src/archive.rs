@@ -0,0 +1,575 @@
+// No Visitors - 导入导出模块
+// 负责整库备份包的生成与恢复，以及明文笔记目录的批量导入/导出
+// 备份包独立于 Git：所有笔记解密后打包为 tar，再用口令派生的密钥整体加密
+
+use crate::crypto::{
+    decrypt_bytes, derive_key_from_passphrase, encrypt_bytes, generate_salt, PASSPHRASE_SALT_LEN,
+};
+use crate::keychain::get_or_create_master_key;
+use crate::storage::{
+    collect_note_files, decrypt_all_with_limit, decrypt_concurrency, ensure_space_available,
+    logical_relative_path, write_encrypted_file, write_files_with_key,
+};
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use tauri::AppHandle;
+
+// 备份包格式：MAGIC (4) + VERSION (1) + SALT (16) + nonce + AES-256-GCM(tar)
+const ARCHIVE_MAGIC: &[u8; 4] = b"VNAR";
+const ARCHIVE_VERSION: u8 = 1;
+const ARCHIVE_HEADER_LEN: usize = 4 + 1 + PASSPHRASE_SALT_LEN;
+// 估算导入所需空间时，每个文件额外计入的字节数（加密头部，以及文件至少占用一个磁盘块）
+const ESTIMATED_FILE_OVERHEAD: u64 = 4096;
+
+/// 估算写入这些内容所需的磁盘空间
+fn estimate_required_space(sizes: impl IntoIterator<Item = u64>) -> u64 {
+    sizes.into_iter().map(|size| size + ESTIMATED_FILE_OVERHEAD).sum()
+}
+
+/// 导入/导出结果摘要
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ArchiveSummary {
+    /// 成功处理的文件数
+    pub file_count: usize,
+    /// 被跳过的文件（相对路径），例如无法解密的文件
+    pub skipped: Vec<String>,
+}
+
+/// 将整个工作区导出为加密备份包
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录路径
+/// - `out_path`: 备份包输出路径
+/// - `passphrase`: 备份口令（恢复时需要）
+/// - `app`: Tauri 应用句柄，用于获取主密钥解密笔记
+/// 
+/// # 返回
+/// 返回导出摘要；无法解密的文件会被跳过并记录
+pub async fn export_vault_archive(
+    workspace_path: &str,
+    out_path: &str,
+    passphrase: &str,
+    app: &AppHandle,
+) -> Result<ArchiveSummary> {
+    let workspace = Path::new(workspace_path);
+    if !workspace.is_dir() {
+        anyhow::bail!("工作区不存在: {}", workspace_path);
+    }
+
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let (output, summary) = build_vault_archive(workspace, &master_key, passphrase).await?;
+
+    if let Some(parent) = Path::new(out_path).parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("无法创建目录: {}", parent.display()))?;
+    }
+    tokio::fs::write(out_path, output)
+        .await
+        .with_context(|| format!("无法写入备份包: {}", out_path))?;
+
+    Ok(summary)
+}
+
+/// 用主密钥解密工作区中的笔记，打包并用口令加密，返回备份包内容
+async fn build_vault_archive(workspace: &Path, key: &[u8], passphrase: &str) -> Result<(Vec<u8>, ArchiveSummary)> {
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut file_count = 0;
+    let mut skipped = Vec::new();
+
+    // 并发解密受 decrypt_concurrency() 限制；收录解密后的原始字节，非文本文件（如图片附件）同样备份
+    let notes = decrypt_all_with_limit(
+        collect_note_files(workspace),
+        decrypt_concurrency(),
+        key,
+        |file| file.bytes,
+    )
+    .await;
+
+    for (file, plaintext) in notes {
+        let relative = logical_relative_path(workspace, &file);
+        let Some(plaintext) = plaintext else {
+            tracing::warn!("[export_vault_archive] 跳过无法解密的文件 {}", relative);
+            skipped.push(relative);
+            continue;
+        };
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(plaintext.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(chrono::Utc::now().timestamp() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, &relative, plaintext.as_slice())
+            .with_context(|| format!("无法写入备份条目: {}", relative))?;
+        file_count += 1;
+    }
+
+    let tar_bytes = builder.into_inner().context("无法完成 tar 打包")?;
+
+    let salt = generate_salt();
+    let archive_key = derive_key_from_passphrase(passphrase, &salt)?;
+    let ciphertext = encrypt_bytes(&tar_bytes, &archive_key).context("加密备份包失败")?;
+
+    let mut output = Vec::with_capacity(ARCHIVE_HEADER_LEN + ciphertext.len());
+    output.extend_from_slice(ARCHIVE_MAGIC);
+    output.push(ARCHIVE_VERSION);
+    output.extend_from_slice(&salt);
+    output.extend_from_slice(&ciphertext);
+
+    Ok((output, ArchiveSummary { file_count, skipped }))
+}
+
+/// 从加密备份包恢复笔记到工作区
+/// 
+/// 备份包中的每个文件会用当前主密钥重新加密写入工作区，同名文件会被覆盖；
+/// 任何一个文件写入失败时，已写入的文件恢复原状（见 `storage::write_files`）。
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录路径
+/// - `archive_path`: 备份包路径
+/// - `passphrase`: 导出时使用的口令
+/// - `app`: Tauri 应用句柄，用于获取主密钥加密笔记
+/// 
+/// # 返回
+/// 返回导入摘要
+pub async fn import_vault_archive(
+    workspace_path: &str,
+    archive_path: &str,
+    passphrase: &str,
+    app: &AppHandle,
+) -> Result<ArchiveSummary> {
+    let data = tokio::fs::read(archive_path)
+        .await
+        .with_context(|| format!("无法读取备份包: {}", archive_path))?;
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let compress = crate::commands::read_workspace_config_or_default(app, "import_vault_archive")
+        .await
+        .compress_notes;
+    restore_vault_archive(Path::new(workspace_path), &data, passphrase, &master_key, compress).await
+}
+
+/// 用口令解密备份包，并用主密钥重新加密写入工作区
+async fn restore_vault_archive(
+    workspace: &Path,
+    data: &[u8],
+    passphrase: &str,
+    key: &[u8],
+    compress: bool,
+) -> Result<ArchiveSummary> {
+    if data.len() < ARCHIVE_HEADER_LEN || &data[..4] != ARCHIVE_MAGIC {
+        anyhow::bail!("不是有效的备份包");
+    }
+    if data[4] != ARCHIVE_VERSION {
+        anyhow::bail!("不支持的备份包版本: {}", data[4]);
+    }
+
+    let salt = &data[5..ARCHIVE_HEADER_LEN];
+    let archive_key = derive_key_from_passphrase(passphrase, salt)?;
+    let tar_bytes = decrypt_bytes(&data[ARCHIVE_HEADER_LEN..], &archive_key)
+        .context("无法解密备份包：口令错误或文件已损坏")?;
+
+    // 先完整解析 tar，避免在写入途中才发现格式错误导致只恢复了一半
+    let mut entries_to_write = Vec::new();
+    let mut archive = tar::Archive::new(tar_bytes.as_slice());
+    for entry in archive.entries().context("无法读取备份包内容")? {
+        let mut entry = entry.context("无法读取备份条目")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let relative = entry.path().context("备份条目路径无效")?.into_owned();
+        // 拒绝绝对路径和 `..`，防止写出工作区
+        if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+            anyhow::bail!("备份条目路径不安全: {}", relative.display());
+        }
+
+        let mut content = Vec::new();
+        entry
+            .read_to_end(&mut content)
+            .with_context(|| format!("无法读取备份条目: {}", relative.display()))?;
+        entries_to_write.push((relative.to_string_lossy().to_string(), content));
+    }
+
+    // 空间不足时在写入任何文件之前失败
+    let required = estimate_required_space(entries_to_write.iter().map(|(_, c)| c.len() as u64));
+    ensure_space_available(workspace, required)?;
+
+    write_files_with_key(workspace, &entries_to_write, key, compress).await?;
+
+    Ok(ArchiveSummary {
+        file_count: entries_to_write.len(),
+        skipped: Vec::new(),
+    })
+}
+
+/// 将外部明文目录（例如 Obsidian 仓库）导入到加密工作区
+/// 
+/// 递归读取 `source_dir` 下的文件，逐个加密写入工作区的 `dest_relative_path` 下，
+/// 保留原有目录结构。隐藏文件/目录（如 `.obsidian`）会被忽略；非 UTF-8 文件（如图片附件）按原始字节加密，
+/// 不做转码。工作区中已存在的同名笔记不会被覆盖，跳过并记录。
+/// 
+/// # 参数
+/// - `source_dir`: 外部明文目录
+/// - `dest_relative_path`: 工作区内的目标目录（相对路径，空字符串表示工作区根目录）
+/// - `app`: Tauri 应用句柄，用于定位工作区和获取主密钥
+/// 
+/// # 返回
+/// 返回导入摘要
+pub async fn import_plaintext_tree(
+    source_dir: &str,
+    dest_relative_path: &str,
+    app: &AppHandle,
+) -> Result<ArchiveSummary> {
+    let source = Path::new(source_dir);
+    if !source.is_dir() {
+        anyhow::bail!("源目录不存在: {}", source_dir);
+    }
+
+    let dest_relative = PathBuf::from(dest_relative_path);
+    if dest_relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+        anyhow::bail!("目标路径必须是工作区内的相对路径: {}", dest_relative_path);
+    }
+
+    let workspace_path = crate::commands::get_workspace_path(app.clone())
+        .map_err(|e| anyhow::anyhow!(e))?;
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let compress = crate::commands::read_workspace_config_or_default(app, "import_plaintext_tree")
+        .await
+        .compress_notes;
+    import_tree_with_key(source, Path::new(&workspace_path), &dest_relative, &master_key, compress).await
+}
+
+/// 用给定的主密钥把外部目录导入到工作区的 `dest_relative` 下
+async fn import_tree_with_key(
+    source: &Path,
+    workspace: &Path,
+    dest_relative: &Path,
+    key: &[u8],
+    compress: bool,
+) -> Result<ArchiveSummary> {
+    let dest_root = workspace.join(dest_relative);
+
+    let source_files: Vec<PathBuf> = walkdir::WalkDir::new(source)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect();
+
+    let required = estimate_required_space(
+        source_files
+            .iter()
+            .map(|f| std::fs::metadata(f).map(|m| m.len()).unwrap_or(0)),
+    );
+    ensure_space_available(&dest_root, required)?;
+
+    let mut file_count = 0;
+    let mut skipped = Vec::new();
+
+    for file in source_files {
+        let relative = file.strip_prefix(source).unwrap_or(&file).to_path_buf();
+
+        // 不覆盖工作区中已有的笔记（加密版本或明文目录中的原始文件）
+        let target = dest_root.join(&relative);
+        if target.exists() || PathBuf::from(format!("{}.enc", target.display())).exists() {
+            tracing::warn!("[import_plaintext_tree] 跳过已存在的笔记: {}", relative.display());
+            skipped.push(relative.to_string_lossy().to_string());
+            continue;
+        }
+
+        let content = match tokio::fs::read(&file).await {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::warn!("[import_plaintext_tree] 跳过无法读取的文件 {}: {}", relative.display(), e);
+                skipped.push(relative.to_string_lossy().to_string());
+                continue;
+            }
+        };
+
+        let target_relative = dest_relative.join(&relative).to_string_lossy().to_string();
+        write_files_with_key(workspace, &[(target_relative, content)], key, compress)
+            .await
+            .with_context(|| format!("无法导入文件: {}", relative.display()))?;
+        file_count += 1;
+    }
+
+    Ok(ArchiveSummary { file_count, skipped })
+}
+
+/// 按指定编码将文本解码为 UTF-8
+/// 
+/// 编码名称使用 WHATWG 标签（如 `gbk`、`gb18030`、`shift_jis`、`big5`、`utf-8`），大小写不敏感；
+/// 文件带有 BOM 时以 BOM 为准。内容中存在该编码下的非法字节时返回错误，避免导入乱码。
+pub fn decode_with_encoding(bytes: &[u8], encoding: &str) -> Result<String> {
+    let encoding = encoding_rs::Encoding::for_label(encoding.trim().as_bytes())
+        .ok_or_else(|| anyhow::anyhow!("不支持的编码: {}", encoding))?;
+    let (content, used, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        anyhow::bail!("文件内容不是有效的 {} 编码", used.name());
+    }
+    Ok(content.into_owned())
+}
+
+/// 以指定编码读取外部文本文件，转码为 UTF-8 后加密导入工作区
+/// 
+/// 用于导入以 GBK、Shift-JIS 等旧编码保存的笔记（`import_plaintext_tree` 按原始字节导入，不转码）
+/// 
+/// # 参数
+/// - `source_path`: 外部文本文件路径
+/// - `dest`: 工作区内的目标文件（相对路径，.enc 后缀可省略）
+/// - `encoding`: 源文件编码，例如 `gbk`、`shift_jis`
+/// - `app`: Tauri 应用句柄，用于定位工作区和获取主密钥
+pub async fn import_file_with_encoding(
+    source_path: &str,
+    dest: &str,
+    encoding: &str,
+    app: &AppHandle,
+) -> Result<()> {
+    let dest_relative = PathBuf::from(dest);
+    if dest_relative.as_os_str().is_empty()
+        || dest_relative.components().any(|c| !matches!(c, Component::Normal(_)))
+    {
+        anyhow::bail!("目标路径必须是工作区内的相对路径: {}", dest);
+    }
+
+    let bytes = tokio::fs::read(source_path)
+        .await
+        .with_context(|| format!("无法读取文件: {}", source_path))?;
+    let content = decode_with_encoding(&bytes, encoding)
+        .with_context(|| format!("无法按 {} 编码解码文件: {}", encoding, source_path))?;
+
+    let workspace_path = crate::commands::get_workspace_path(app.clone())
+        .map_err(|e| anyhow::anyhow!(e))?;
+    let target = Path::new(&workspace_path).join(&dest_relative);
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("无法创建目录: {}", parent.display()))?;
+    }
+    write_encrypted_file(target.to_string_lossy().as_ref(), &content, app)
+        .await
+        .with_context(|| format!("无法导入文件: {}", dest))?;
+
+    tracing::info!("[import_file_with_encoding] 已按 {} 编码导入 {} -> {}", encoding, source_path, dest);
+    Ok(())
+}
+
+/// 拒绝位于工作区内的明文导出目录，否则明文会被下一次自动提交写入仓库
+/// 
+/// 目录可以尚不存在：按最近的已存在上级目录解析（见 `storage::resolve_path`）
+fn ensure_outside_workspace(workspace: &Path, dest: &Path) -> Result<()> {
+    let workspace_canonical = workspace
+        .canonicalize()
+        .with_context(|| format!("无法解析工作区路径: {}", workspace.display()))?;
+    let dest_absolute = std::path::absolute(dest)
+        .with_context(|| format!("无法解析导出目录: {}", dest.display()))?;
+    let dest_resolved = crate::storage::resolve_path(&dest_absolute)?;
+    if dest_resolved.starts_with(&workspace_canonical) {
+        anyhow::bail!("导出目录不能位于工作区内，否则明文会被提交到仓库");
+    }
+    Ok(())
+}
+
+/// 将整个工作区解密导出为明文目录（用于迁移到其他工具）
+/// 
+/// 遍历所有笔记（见 `storage::collect_note_files`），解密后去掉 .enc 后缀写入 `dest_dir`，保留目录结构；
+/// 明文目录中的文件原样复制。
+/// 按字节解密，因此二进制文件同样可以导出；无法解密的文件会被跳过并记录，不会中断导出。
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录路径
+/// - `dest_dir`: 明文导出目标目录
+/// - `app`: Tauri 应用句柄，用于获取主密钥
+/// 
+/// # 返回
+/// 返回导出摘要
+pub async fn export_plaintext_tree(
+    workspace_path: &str,
+    dest_dir: &str,
+    app: &AppHandle,
+) -> Result<ArchiveSummary> {
+    let workspace = Path::new(workspace_path);
+    if !workspace.is_dir() {
+        anyhow::bail!("工作区不存在: {}", workspace_path);
+    }
+
+    let dest = Path::new(dest_dir);
+    ensure_outside_workspace(workspace, dest)?;
+
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+
+    let mut file_count = 0;
+    let mut skipped = Vec::new();
+
+    // 解密后直接在回调中写出，内存中同时只保留 decrypt_concurrency() 个文件的明文
+    let dest_root = dest.to_path_buf();
+    let workspace_root = workspace.to_path_buf();
+    let results = decrypt_all_with_limit(
+        collect_note_files(workspace),
+        decrypt_concurrency(),
+        &master_key,
+        move |file| -> Result<()> {
+            let target = dest_root.join(logical_relative_path(&workspace_root, file.path));
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("无法创建目录: {}", parent.display()))?;
+            }
+            std::fs::write(&target, &file.bytes)
+                .with_context(|| format!("无法写入文件: {}", target.display()))
+        },
+    )
+    .await;
+
+    for (file, written) in results {
+        match written {
+            Some(written) => {
+                written?;
+                file_count += 1;
+            }
+            None => {
+                let relative = logical_relative_path(workspace, &file);
+                tracing::warn!("[export_plaintext_tree] 跳过无法解密的文件 {}", relative);
+                skipped.push(relative);
+            }
+        }
+    }
+
+    Ok(ArchiveSummary { file_count, skipped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_workspace, write_note};
+
+    #[test]
+    fn test_decode_with_encoding_transcodes_legacy_text() {
+        // "中文笔记" 的 GBK 编码
+        let gbk = [0xD6, 0xD0, 0xCE, 0xC4, 0xB1, 0xCA, 0xBC, 0xC7];
+        assert_eq!(decode_with_encoding(&gbk, "GBK").unwrap(), "中文笔记");
+        // "日本" 的 Shift-JIS 编码
+        assert_eq!(decode_with_encoding(&[0x93, 0xFA, 0x96, 0x7B], "shift_jis").unwrap(), "日本");
+        // BOM 优先于指定的编码
+        assert_eq!(decode_with_encoding(&[0xEF, 0xBB, 0xBF, b'h', b'i'], "gbk").unwrap(), "hi");
+
+        assert!(decode_with_encoding(&[0xFF, 0xFE, 0xFD], "utf-8").is_err());
+        assert!(decode_with_encoding(b"abc", "no-such-encoding").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_vault_archive_round_trip() {
+        let source = temp_workspace("roundtrip_src");
+        let key = [7u8; 32];
+        write_note(&source, "top.md.enc", "top", &key);
+        write_note(&source, "a/b/c/deep.md.enc", "深层笔记", &key);
+        write_note(&source, "a/foreign.md.enc", "x", &[9u8; 32]);
+        write_note(&source, ".git/ignored.enc", "x", &key);
+        std::fs::write(source.join("a/image.png.enc"), encrypt_bytes(&[0x89, b'P', 0xFF, 0x00], &key).unwrap()).unwrap();
+
+        let (archive, summary) = build_vault_archive(&source, &key, "correct horse").await.unwrap();
+        assert_eq!(summary.file_count, 3);
+        assert_eq!(summary.skipped, vec!["a/foreign.md"]);
+
+        // 恢复到另一台设备：使用不同的主密钥重新加密
+        let dest = temp_workspace("roundtrip_dest");
+        let new_key = [3u8; 32];
+        let err = restore_vault_archive(&dest, &archive, "wrong", &new_key, false).await.unwrap_err();
+        assert!(err.to_string().contains("口令错误"));
+        assert!(restore_vault_archive(&dest, b"VNAR", "correct horse", &new_key, false).await.is_err());
+        assert!(crate::storage::collect_encrypted_files(&dest).is_empty(), "失败时不应写入任何文件");
+
+        let restored = restore_vault_archive(&dest, &archive, "correct horse", &new_key, true).await.unwrap();
+        assert_eq!(restored.file_count, 3);
+        let files = crate::storage::collect_encrypted_files(&dest);
+        assert_eq!(
+            files,
+            vec![dest.join("a/b/c/deep.md.enc"), dest.join("a/image.png.enc"), dest.join("top.md.enc")]
+        );
+        let read = |path: &str| {
+            let ciphertext = std::fs::read(dest.join(path)).unwrap();
+            crate::crypto::decrypt_note(&ciphertext, &new_key).unwrap().0
+        };
+        assert_eq!(read("a/b/c/deep.md.enc"), "深层笔记");
+        assert_eq!(read("top.md.enc"), "top");
+        let image = crate::crypto::decrypt_note_bytes(&std::fs::read(dest.join("a/image.png.enc")).unwrap(), &new_key).unwrap().0;
+        assert_eq!(image, [0x89, b'P', 0xFF, 0x00]);
+    }
+
+    #[tokio::test]
+    async fn test_vault_archive_round_trip_keeps_plaintext_notes() {
+        let _guard = crate::storage::PLAINTEXT_FOLDERS_TEST_LOCK.lock().await;
+        crate::storage::set_plaintext_folders(&["Public".to_string()]);
+        let source = temp_workspace("plaintext_src");
+        let key = [7u8; 32];
+        std::fs::create_dir_all(source.join("Public/sub")).unwrap();
+        std::fs::write(source.join("Public/sub/readme.md"), "公开笔记").unwrap();
+        std::fs::write(source.join("secret.md.enc"), crate::crypto::encrypt_content("secret", &key).unwrap()).unwrap();
+
+        let (archive, summary) = build_vault_archive(&source, &key, "pass").await.unwrap();
+        assert_eq!(summary.file_count, 2);
+        assert!(summary.skipped.is_empty());
+
+        let dest = temp_workspace("plaintext_dest");
+        let restored = restore_vault_archive(&dest, &archive, "pass", &key, false).await.unwrap();
+        assert_eq!(restored.file_count, 2);
+        assert_eq!(std::fs::read_to_string(dest.join("Public/sub/readme.md")).unwrap(), "公开笔记");
+        assert!(!dest.join("Public/sub/readme.md.enc").exists());
+        let ciphertext = std::fs::read(dest.join("secret.md.enc")).unwrap();
+        assert_eq!(crate::crypto::decrypt_note(&ciphertext, &key).unwrap().0, "secret");
+
+        crate::storage::set_plaintext_folders(&[]);
+    }
+
+    #[tokio::test]
+    async fn test_import_tree_keeps_binaries_and_existing_notes() {
+        let source = temp_workspace("import_tree_src");
+        let workspace = temp_workspace("import_tree_dest");
+        let key = [5u8; 32];
+        std::fs::create_dir_all(source.join("attachments")).unwrap();
+        std::fs::create_dir_all(source.join(".obsidian")).unwrap();
+        std::fs::write(source.join("note.md"), "新笔记").unwrap();
+        std::fs::write(source.join("existing.md"), "外部版本").unwrap();
+        std::fs::write(source.join("attachments/image.png"), [0x89, b'P', b'N', b'G', 0xFF, 0x00]).unwrap();
+        std::fs::write(source.join(".obsidian/app.json"), "{}").unwrap();
+        write_note(&workspace, "imported/existing.md.enc", "工作区版本", &key);
+
+        let summary = import_tree_with_key(&source, &workspace, Path::new("imported"), &key, false).await.unwrap();
+        assert_eq!(summary.file_count, 2);
+        assert_eq!(summary.skipped, vec!["existing.md"]);
+
+        let read = |path: &str| {
+            let ciphertext = std::fs::read(workspace.join(path)).unwrap();
+            crate::crypto::decrypt_note_bytes(&ciphertext, &key).unwrap().0
+        };
+        assert_eq!(read("imported/note.md.enc"), "新笔记".as_bytes());
+        assert_eq!(read("imported/attachments/image.png.enc"), [0x89, b'P', b'N', b'G', 0xFF, 0x00]);
+        assert_eq!(read("imported/existing.md.enc"), "工作区版本".as_bytes());
+        assert!(!workspace.join("imported/.obsidian").exists());
+    }
+
+    #[test]
+    fn test_plaintext_export_rejects_destination_inside_workspace() {
+        let root = temp_workspace("export_dest");
+        let workspace = root.join("vault");
+        std::fs::create_dir_all(workspace.join("notes")).unwrap();
+
+        // 目标目录尚不存在时同样要拒绝
+        assert!(ensure_outside_workspace(&workspace, &workspace.join("export")).is_err());
+        assert!(ensure_outside_workspace(&workspace, &workspace.join("notes/deep/export")).is_err());
+        assert!(ensure_outside_workspace(&workspace, &workspace.join("notes")).is_err());
+        assert!(ensure_outside_workspace(&workspace, &workspace).is_err());
+        assert!(ensure_outside_workspace(&workspace, &root.join("export")).is_ok());
+        assert!(ensure_outside_workspace(&workspace, &root.join("vault-export/new")).is_ok());
+    }
+}
@@ -2,32 +2,157 @@
 // 将所有 Rust 功能暴露给前端 JavaScript/TypeScript
 // 每个命令都对应一个可以被前端调用的函数
 
+use crate::archive::{
+    export_plaintext_tree, export_vault_archive, import_file_with_encoding, import_plaintext_tree,
+    import_vault_archive, ArchiveSummary,
+};
+use crate::events;
 use crate::git::{
-    abort_sync, commit_changes, continue_sync, get_commit_history, get_current_branch,
-    get_repository_status, git_gc, init_repository, resolve_conflict, switch_to_branch,
-    verify_repository, ConflictResolutionItem, SyncResult,
+    abort_sync, changed_files_since, check_case_collisions, check_worktree_consistency,
+    cherry_pick, commit_changes_if_changed, commit_paths, continue_sync, count_commits,
+    count_missing_blobs, create_merge_commit, detect_default_branch, discard_worktree_changes,
+    estimate_sync_size, find_large_objects, first_commit_for_path, flatten_history,
+    get_ahead_behind, get_commit_excludes, get_commit_history, get_current_branch, get_git_config,
+    get_head_commit, get_oplog, get_path_status, get_remote_auth_format, get_repository_status,
+    git_gc, hard_reset_to, has_changes_to_commit, init_repository, inspect_object, list_conflicts,
+    list_tree, list_unsynced_changes, merge_branch, pull, purge_path_from_history, remote_head,
+    remove_from_index, rename_branch, render_commit_message, resolve_conflict,
+    resolve_conflict_branch, sanitize_config_credentials, set_auto_gc_after_commits,
+    set_commit_excludes, set_git_config, set_network_timeouts, set_remote_auth_format,
+    shelve_changes, soft_reset_last_commit, squash_unpushed_commits, switch_to_branch,
+    undo_last_operation, unshelve_changes, verify_remote_branch, verify_repository, ChangedFiles,
+    CommitCount, ConflictBranchAction, ConflictBranchInfo, ConflictResolutionChoice,
+    ConflictResolutionItem, FlattenResult, HeadCommit, LargeObject, MergeResult, ObjectInfo,
+    PatAuthFormat, PathStatus, PurgeResult, SquashResult, SyncCapabilities, SyncResult,
+    SyncSizeEstimate, TreeEntryInfo, UnshelveResult, WorktreeConsistency,
 };
-use crate::keychain::{store_pat_token, get_pat_token, remove_pat_token, has_pat_token};
+use crate::github::PatScopeCheck;
+use crate::keychain::{get_or_create_master_key, store_pat_token, get_pat_token, remove_pat_token, has_pat_token};
+use crate::locks::{lock_repo, lock_repo_blocking};
+use crate::oplog::OpLogEntry;
+use crate::search_index::{rebuild_search_index, search_indexed, SearchIndexStats, SearchPage};
 use crate::storage::{
-    copy_file_or_directory, create_directory, create_file, delete_directory, delete_file, list_directory,
-    move_file_or_directory, read_encrypted_file, rename_file_or_directory, write_encrypted_file, FileInfo,
-    search_files, SearchResult,
+    build_backlinks, confine_to_workspace, convert_plaintext_folder_notes, copy_file_or_directory,
+    create_directory, create_file, create_file_from_template, delete_directory, delete_file,
+    delete_file_and_prune, file_content_hash, find_atmosphere_config, find_duplicates, get_available_space,
+    get_vault_stats, index_tags, is_plaintext_path, list_directory, move_between_vaults, move_file_or_directory,
+    read_encrypted_file, read_file_meta, read_file_preview, read_files_batch, read_plaintext_file, rekey_file,
+    rename_file_or_directory, rename_paths_in_list, rename_with_link_update, scan_vault_integrity, search_files,
+    set_copy_limits, set_decrypt_concurrency, set_plaintext_folders, workspace_relative_path,
+    write_encrypted_file, write_files, write_plaintext_file, BacklinkIndex, BatchReadResult,
+    DEFAULT_PREVIEW_CHARS, DuplicateGroup, FileInfo, FileMeta, SearchResult, TagIndex, VaultIntegrityReport,
+    VaultStats,
 };
+use crate::vaults::{VaultEntry, VaultRegistry};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tauri::{AppHandle, Manager};
 
-/// 读取加密文件
+/// 校验前端传入的路径位于工作区内，拒绝 `../../etc/passwd` 之类的路径穿越
+fn ensure_in_workspace(app: &AppHandle, path: &str) -> Result<(), String> {
+    let workspace_path = get_workspace_path(app.clone())?;
+    confine_to_workspace(std::path::Path::new(&workspace_path), std::path::Path::new(path))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// 校验路径位于工作区内，并返回它是否位于明文目录中（见 `WorkspaceConfig::plaintext_folders`）
+fn in_plaintext_folder(app: &AppHandle, path: &str) -> Result<bool, String> {
+    let workspace_path = get_workspace_path(app.clone())?;
+    let relative = repo_relative_path(std::path::Path::new(&workspace_path), path)?;
+    Ok(is_plaintext_path(&relative))
+}
+
+/// 持有仓库锁，在阻塞线程池中执行 Git 操作
+/// 
+/// 需要先 await 密钥/配置的 async 命令必须通过它调用 Git：检出等操作会调用
+/// `lock_workspace_blocking()`，在 tokio 工作线程上直接调用会 panic。
+async fn run_git_blocking<T, F>(operation: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    let repo_lock = lock_repo().await;
+    crate::locks::spawn_blocking(move || {
+        let _repo_lock = repo_lock;
+        operation()
+    })
+    .await
+    .map_err(|e| format!("Git 操作异常终止: {}", e))?
+    .map_err(|e| e.to_string())
+}
+
+/// 读取加密文件（明文目录中的文件直接读取，不解密）
 /// 
 /// 前端调用: `invoke('read_file', { path: '...' })`
 #[tauri::command]
 pub async fn read_file(path: String, app: AppHandle) -> Result<String, String> {
+    if in_plaintext_folder(&app, &path)? {
+        return read_plaintext_file(&path, &app)
+            .await
+            .map_err(|e| e.to_string());
+    }
     read_encrypted_file(&path, &app)
         .await
         .map_err(|e| e.to_string())
 }
 
-/// 写入加密文件
+/// 读取笔记的创建/最后编辑时间（保存在密文内，不受 checkout 重置 mtime 影响）
+/// 
+/// 前端调用: `invoke('read_file_meta', { path: '...' })`
+#[tauri::command]
+pub async fn read_file_meta_command(path: String, app: AppHandle) -> Result<FileMeta, String> {
+    ensure_in_workspace(&app, &path)?;
+    read_file_meta(&path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 计算文件解密后内容的哈希（用于检测磁盘上的文件是否被同步等操作改变）
+/// 
+/// 前端调用: `invoke('file_content_hash_command', { workspacePath: '...', relativePath: 'notes/a.md' })`
+#[tauri::command]
+pub async fn file_content_hash_command(
+    workspace_path: String,
+    relative_path: String,
+    app: AppHandle,
+) -> Result<String, String> {
+    file_content_hash(&workspace_path, &relative_path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 读取笔记开头的一段内容（用于列表预览，默认 200 个字符）
+/// 
+/// 前端调用: `invoke('read_file_preview_command', { workspacePath: '...', relativePath: 'notes/a.md', maxChars: 200 })`
+#[tauri::command]
+pub async fn read_file_preview_command(
+    workspace_path: String,
+    relative_path: String,
+    max_chars: Option<usize>,
+    app: AppHandle,
+) -> Result<String, String> {
+    let max_chars = max_chars.unwrap_or(DEFAULT_PREVIEW_CHARS);
+    read_file_preview(&workspace_path, &relative_path, max_chars, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 一次读取多个笔记，返回 路径 -> 内容（或该文件的错误）
+/// 
+/// 前端调用: `invoke('read_files_batch_command', { workspacePath: '...', relativePaths: ['notes/a.md', 'notes/b.md'] })`
+#[tauri::command]
+pub async fn read_files_batch_command(
+    workspace_path: String,
+    relative_paths: Vec<String>,
+    app: AppHandle,
+) -> Result<std::collections::BTreeMap<String, BatchReadResult>, String> {
+    read_files_batch(&workspace_path, &relative_paths, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 写入加密文件（明文目录中的文件不加密，写入不带 .enc 后缀的文件）
 /// 
 /// 前端调用: `invoke('write_file', { path: '...', content: '...' })`
 #[tauri::command]
@@ -36,11 +161,41 @@ pub async fn write_file(
     content: String,
     app: AppHandle,
 ) -> Result<(), String> {
+    if in_plaintext_folder(&app, &path)? {
+        return write_plaintext_file(&path, &content)
+            .await
+            .map_err(|e| e.to_string());
+    }
     write_encrypted_file(&path, &content, &app)
         .await
         .map_err(|e| e.to_string())
 }
 
+/// 加密写入多个文件并生成一个只包含这些文件的提交（例如一次创建一组模板笔记）
+/// 
+/// 明文目录中的文件不加密。任何一个文件写入失败时不会留下部分写入的文件，也不会提交。
+/// 
+/// 前端调用: `invoke('write_files_and_commit_command', { workspacePath: '...', files: [['notes/a.md', '...'], ['notes/b.md', '...']], message: '...' })`
+#[tauri::command]
+pub async fn write_files_and_commit_command(
+    workspace_path: String,
+    files: Vec<(String, String)>,
+    message: String,
+    app: AppHandle,
+) -> Result<String, String> {
+    let _repo_lock = lock_repo().await;
+    let written = write_files(&workspace_path, &files, &app)
+        .await
+        .map_err(|e| e.to_string())?;
+    let outcome = commit_paths(std::path::Path::new(&workspace_path), &written, &message, "main")
+        .map_err(|e| format!("git commit 失败: {}", e))?;
+    if outcome.created {
+        events::emit_commit_done(&app, &outcome.sha, &message);
+        crate::search_index::refresh_search_index_in_background(app, workspace_path);
+    }
+    Ok(outcome.sha)
+}
+
 /// 列出目录内容
 /// 
 /// 前端调用: `invoke('list_directory', { path: '...' })`
@@ -56,6 +211,7 @@ pub async fn list_directory_command(path: String) -> Result<Vec<FileInfo>, Strin
 /// 前端调用: `invoke('init_repository', { path: '...' })`
 #[tauri::command]
 pub fn init_repository_command(path: String) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
     init_repository(PathBuf::from(path).as_path())
         .map_err(|e| e.to_string())
 }
@@ -64,11 +220,124 @@ pub fn init_repository_command(path: String) -> Result<(), String> {
 /// 
 /// 前端调用: `invoke('commit_changes', { path: '...', message: '...' })`
 #[tauri::command]
-pub fn commit_changes_command(path: String, message: String) -> Result<String, String> {
-    commit_changes(PathBuf::from(path).as_path(), &message)
+pub fn commit_changes_command(path: String, message: String, app: AppHandle) -> Result<String, String> {
+    let _repo_lock = lock_repo_blocking();
+    let outcome = commit_changes_if_changed(PathBuf::from(&path).as_path(), &message, "main")
+        .map_err(|e| e.to_string())?;
+    if outcome.created {
+        events::emit_commit_done(&app, &outcome.sha, &message);
+        crate::search_index::refresh_search_index_in_background(app, path);
+    }
+    Ok(outcome.sha)
+}
+
+/// 检查提交是否会产生新提交（不写入索引，不创建提交）
+/// 
+/// 前端调用: `invoke('has_changes_to_commit_command', { path: '...' })`
+#[tauri::command]
+pub fn has_changes_to_commit_command(path: String) -> Result<bool, String> {
+    let _repo_lock = lock_repo_blocking();
+    has_changes_to_commit(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 获取操作日志（每次提交、同步、合并前后分支指向的提交）
+/// 
+/// 前端调用: `invoke('get_oplog_command', { path: '...' })`
+#[tauri::command]
+pub fn get_oplog_command(path: String) -> Result<Vec<OpLogEntry>, String> {
+    get_oplog(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 撤销最近一次操作（把分支恢复到操作之前的提交，例如撤销一次错误的同步）
+/// 
+/// 前端调用: `invoke('undo_last_operation_command', { path: '...' })`
+#[tauri::command]
+pub fn undo_last_operation_command(path: String) -> Result<OpLogEntry, String> {
+    let _repo_lock = lock_repo_blocking();
+    undo_last_operation(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 撤销最近一次提交，修改保留在工作区中（soft reset）
+/// 
+/// 前端调用: `invoke('soft_reset_last_commit_command', { path: '...' })`
+#[tauri::command]
+pub fn soft_reset_last_commit_command(path: String) -> Result<String, String> {
+    let _repo_lock = lock_repo_blocking();
+    soft_reset_last_commit(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 把当前分支重置到指定提交并覆盖工作区（丢弃所有未提交的修改，需要 `confirm: true`）
+/// 
+/// 前端调用: `invoke('hard_reset_to_command', { path: '...', commitSha: '...', confirm: true })`
+#[tauri::command]
+pub fn hard_reset_to_command(path: String, commit_sha: String, confirm: bool) -> Result<String, String> {
+    let _repo_lock = lock_repo_blocking();
+    hard_reset_to(PathBuf::from(path).as_path(), &commit_sha, confirm)
+        .map_err(|e| e.to_string())
+}
+
+/// 放弃所有未提交的修改，恢复到最新提交（删除未跟踪的新文件，需要 `confirm: true`）
+/// 
+/// 前端调用: `invoke('discard_worktree_changes_command', { path: '...', confirm: true })`
+#[tauri::command]
+pub fn discard_worktree_changes_command(path: String, confirm: bool) -> Result<Vec<String>, String> {
+    let _repo_lock = lock_repo_blocking();
+    discard_worktree_changes(PathBuf::from(path).as_path(), confirm)
+        .map_err(|e| e.to_string())
+}
+
+/// 获取只保留在本地、不参与提交的路径模式
+/// 
+/// 前端调用: `invoke('get_commit_excludes_command', { path: '...' })`
+#[tauri::command]
+pub fn get_commit_excludes_command(path: String) -> Result<Vec<String>, String> {
+    get_commit_excludes(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 设置只保留在本地、不参与提交的路径模式（例如按流量计费时不同步 `attachments/`）
+/// 
+/// 前端调用: `invoke('set_commit_excludes_command', { path: '...', patterns: ['attachments/'] })`
+#[tauri::command]
+pub fn set_commit_excludes_command(path: String, patterns: Vec<String>) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
+    set_commit_excludes(PathBuf::from(path).as_path(), &patterns)
         .map_err(|e| e.to_string())
 }
 
+/// 自动提交（定时提交使用）
+/// 
+/// 提交消息按工作区配置中的 commit_message_template 生成，提交到配置的分支（未配置时自动检测）
+/// 
+/// 前端调用: `invoke('auto_commit_command', { path: '...' })`
+#[tauri::command]
+pub async fn auto_commit_command(path: String, app: AppHandle) -> Result<String, String> {
+    let config = read_workspace_config_or_default(&app, "auto_commit_command").await;
+    let repo_path = PathBuf::from(path);
+
+    let _repo_lock = lock_repo().await;
+    let branch_name = match config.branch_name {
+        Some(branch) => branch,
+        None => detect_default_branch(&repo_path, &config.remote_name)
+            .unwrap_or_else(|_| "main".to_string()),
+    };
+    let changed_files = get_repository_status(&repo_path)
+        .map(|status| status.changed_files)
+        .unwrap_or(0);
+    let message = render_commit_message(&config.commit_message_template, changed_files, &branch_name);
+    let outcome = commit_changes_if_changed(&repo_path, &message, &branch_name)
+        .map_err(|e| e.to_string())?;
+    if outcome.created {
+        events::emit_commit_done(&app, &outcome.sha, &message);
+        crate::search_index::refresh_search_index_in_background(app, repo_path.to_string_lossy().to_string());
+    }
+    Ok(outcome.sha)
+}
+
 /// 获取仓库状态
 /// 
 /// 前端调用: `invoke('get_repository_status', { path: '...' })`
@@ -78,15 +347,74 @@ pub fn get_repository_status_command(path: String) -> Result<crate::git::GitStat
         .map_err(|e| e.to_string())
 }
 
+/// 获取单个文件的 Git 状态
+/// 
+/// 前端调用: `invoke('get_path_status_command', { path: '...', relativePath: 'notes/a.md.enc' })`
+#[tauri::command]
+pub fn get_path_status_command(path: String, relative_path: String) -> Result<PathStatus, String> {
+    get_path_status(PathBuf::from(path).as_path(), &relative_path)
+        .map_err(|e| e.to_string())
+}
+
+/// 检查工作区文件是否与 HEAD 一致，报告内容不同和缺失的文件
+/// 
+/// 前端调用: `invoke('check_worktree_consistency_command', { path: '...' })`
+#[tauri::command]
+pub async fn check_worktree_consistency_command(
+    path: String,
+    app: AppHandle,
+) -> Result<WorktreeConsistency, String> {
+    let master_key = get_or_create_master_key(&app)
+        .await
+        .map_err(|e| format!("无法获取主加密密钥: {}", e))?;
+    let _repo_lock = lock_repo().await;
+    check_worktree_consistency(PathBuf::from(path).as_path(), &master_key)
+        .map_err(|e| e.to_string())
+}
+
 /// 执行 Git GC
 /// 
 /// 前端调用: `invoke('git_gc', { path: '...' })`
 #[tauri::command]
 pub fn git_gc_command(path: String) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
     git_gc(PathBuf::from(path).as_path())
         .map_err(|e| e.to_string())
 }
 
+/// 将全部历史压缩为一个提交（缩小 .git 体积）
+/// 
+/// 警告：压缩后本地历史与远端分叉，必须用 `force_push_with_lease` 覆盖远端（在此之前同步会被拒绝），其他设备需要重新克隆。
+/// 
+/// 前端调用: `invoke('flatten_history_command', { path: '...' })`
+#[tauri::command]
+pub fn flatten_history_command(path: String) -> Result<FlattenResult, String> {
+    let _repo_lock = lock_repo_blocking();
+    flatten_history(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 列出历史中不小于 minSize 字节的文件（以及引用它们的路径和提交）
+/// 
+/// 前端调用: `invoke('find_large_objects_command', { path: '...', minSize: 10485760 })`
+#[tauri::command]
+pub fn find_large_objects_command(path: String, min_size: u64) -> Result<Vec<LargeObject>, String> {
+    find_large_objects(PathBuf::from(path).as_path(), min_size)
+        .map_err(|e| e.to_string())
+}
+
+/// 从当前分支的全部历史中删除指定路径并执行 GC（缩小 .git 体积）
+/// 
+/// 警告：改写后本地历史与远端分叉，必须用 `force_push_with_lease` 覆盖远端（在此之前同步会被拒绝），其他设备需要重新克隆。
+/// 
+/// 前端调用: `invoke('purge_path_from_history_command', { path: '...', target: 'media/big.bin', confirm: true })`
+#[tauri::command]
+pub fn purge_path_from_history_command(path: String, target: String, confirm: bool) -> Result<PurgeResult, String> {
+    let _repo_lock = lock_repo_blocking();
+    purge_path_from_history(PathBuf::from(path).as_path(), &target, confirm)
+        .map_err(|e| e.to_string())
+}
+
 /// 验证 Git 仓库
 /// 
 /// 前端调用: `invoke('verify_repository', { path: '...' })`
@@ -96,32 +424,107 @@ pub fn verify_repository_command(path: String) -> Result<crate::git::RepositoryV
         .map_err(|e| e.to_string())
 }
 
+/// 查看 Git 对象的类型和未压缩大小（调试用，支持 SHA 或 `HEAD:path` 等 revspec）
+/// 
+/// 前端调用: `invoke('inspect_object_command', { path: '...', oid: 'HEAD:notes/a.md.enc' })`
+#[tauri::command]
+pub fn inspect_object_command(path: String, oid: String) -> Result<ObjectInfo, String> {
+    inspect_object(PathBuf::from(path).as_path(), &oid)
+        .map_err(|e| e.to_string())
+}
+
+/// 列出某个提交中指定目录下的条目（浏览历史快照，不检出）
+/// 
+/// 前端调用: `invoke('list_tree_command', { path: '...', commitSha: '...', subpath: 'notes' })`
+#[tauri::command]
+pub fn list_tree_command(
+    path: String,
+    commit_sha: String,
+    subpath: Option<String>,
+) -> Result<Vec<TreeEntryInfo>, String> {
+    list_tree(PathBuf::from(path).as_path(), &commit_sha, subpath.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// 检查某个提交中仅大小写不同的路径（在 Windows/macOS 上检出会互相覆盖）
+/// 
+/// 前端调用: `invoke('check_case_collisions_command', { path: '...', rev: 'HEAD' })`
+#[tauri::command]
+pub fn check_case_collisions_command(path: String, rev: Option<String>) -> Result<Vec<Vec<String>>, String> {
+    check_case_collisions(PathBuf::from(path).as_path(), rev.as_deref())
+        .map_err(|e| e.to_string())
+}
+
 /// 获取提交历史
 /// 
-/// 前端调用: `invoke('get_commit_history', { path: '...', limit: 10 })`
+/// 分页时把上一页最后一条的 sha 作为 beforeSha 传入
+/// 
+/// 前端调用: `invoke('get_commit_history', { path: '...', limit: 10, beforeSha: '...' })`
+#[tauri::command]
+pub fn get_commit_history_command(
+    path: String,
+    limit: Option<usize>,
+    before_sha: Option<String>,
+) -> Result<Vec<crate::git::CommitInfo>, String> {
+    get_commit_history(PathBuf::from(path).as_path(), limit, before_sha.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// 列出从指定提交到 HEAD 之间新增/修改/删除的文件
+/// 
+/// 前端调用: `invoke('changed_files_since_command', { path: '...', sinceSha: '...' })`
+#[tauri::command]
+pub fn changed_files_since_command(path: String, since_sha: String) -> Result<ChangedFiles, String> {
+    changed_files_since(PathBuf::from(path).as_path(), &since_sha)
+        .map_err(|e| e.to_string())
+}
+
+/// 查找引入文件的提交（文件首次出现的提交），从未出现时返回 null
+/// 
+/// 前端调用: `invoke('first_commit_for_path_command', { path: '...', relativePath: '...' })`
+#[tauri::command]
+pub fn first_commit_for_path_command(
+    path: String,
+    relative_path: String,
+) -> Result<Option<crate::git::CommitInfo>, String> {
+    first_commit_for_path(PathBuf::from(path).as_path(), &relative_path)
+        .map_err(|e| e.to_string())
+}
+
+/// 获取当前 HEAD 提交（sha 与短 sha），仓库尚无提交时返回 null
+/// 
+/// 前端调用: `invoke('get_head_commit_command', { path: '...' })`
+#[tauri::command]
+pub fn get_head_commit_command(path: String) -> Result<Option<HeadCommit>, String> {
+    get_head_commit(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 获取提交总数（有上限，用于显示“共 N 条”）
+/// 
+/// 前端调用: `invoke('get_commit_count_command', { path: '...', maxCount: 10000 })`
 #[tauri::command]
-pub fn get_commit_history_command(path: String, limit: Option<usize>) -> Result<Vec<crate::git::CommitInfo>, String> {
-    get_commit_history(PathBuf::from(path).as_path(), limit)
+pub fn get_commit_count_command(path: String, max_count: Option<usize>) -> Result<CommitCount, String> {
+    count_commits(PathBuf::from(path).as_path(), max_count)
         .map_err(|e| e.to_string())
 }
 
 /// 读取氛围协议配置
 /// 
+/// 目录自身没有 .vnode.json 时继承最近的上级目录（直到工作区根目录）的配置
+/// 
 /// 前端调用: `invoke('read_atmosphere_config', { path: '...' })`
 #[tauri::command]
-pub async fn read_atmosphere_config(path: String, _app: AppHandle) -> Result<AtmosphereConfig, String> {
-    // .vnode.json 文件是未加密的 JSON 文件
-    let config_path = PathBuf::from(&path).join(".vnode.json");
-    
-    if !config_path.exists() {
+pub async fn read_atmosphere_config(path: String, app: AppHandle) -> Result<AtmosphereConfig, String> {
+    // .vnode.json 可能是明文 JSON，也可能已按工作区配置加密
+    let workspace_path = get_workspace_path(app.clone())?;
+    let Some(config_path) = find_atmosphere_config(std::path::Path::new(&workspace_path), std::path::Path::new(&path)) else {
         return Ok(AtmosphereConfig {
-            theme: "arcane".to_string(),
+            theme: DEFAULT_ATMOSPHERE_THEME.to_string(),
         });
-    }
+    };
 
-    let content = tokio::fs::read_to_string(&config_path)
-        .await
-        .map_err(|e| format!("无法读取配置文件: {}", e))?;
+    let content = read_config_file(&config_path, &app).await?;
 
     let config: AtmosphereConfig = serde_json::from_str(&content)
         .map_err(|e| format!("无法解析配置文件: {}", e))?;
@@ -136,7 +539,17 @@ pub async fn read_atmosphere_config(path: String, _app: AppHandle) -> Result<Atm
 pub async fn write_atmosphere_config(
     path: String,
     config: AtmosphereConfig,
+    app: AppHandle,
 ) -> Result<(), String> {
+    // 未知主题在前端会被静默回退，这里直接拒绝，避免把错误的值写进 .vnode.json
+    if !ATMOSPHERE_THEMES.contains(&config.theme.as_str()) {
+        return Err(format!(
+            "未知的氛围主题: {}（可用: {}）",
+            config.theme,
+            ATMOSPHERE_THEMES.join(", ")
+        ));
+    }
+
     let config_path = PathBuf::from(&path).join(".vnode.json");
 
     // 确保目录存在
@@ -149,13 +562,90 @@ pub async fn write_atmosphere_config(
     let content = serde_json::to_string_pretty(&config)
         .map_err(|e| format!("无法序列化配置: {}", e))?;
 
-    tokio::fs::write(&config_path, content)
+    let encrypt = read_workspace_config_or_default(&app, "write_atmosphere_config")
         .await
-        .map_err(|e| format!("无法写入配置文件: {}", e))?;
+        .encrypt_config_files;
+    write_config_file(&config_path, &content, encrypt, &app).await
+}
 
-    Ok(())
+/// 读取配置文件（.vnode.json / settings.json）
+/// 
+/// 内容是合法 JSON 时视为明文（旧文件或未开启加密），否则用主密钥解密。
+/// 解密只使用已保存的主密钥：本机没有密钥时报错，不能生成新密钥（新密钥永远解不开这个文件）
+async fn read_config_file(path: &std::path::Path, app: &AppHandle) -> Result<String, String> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("无法读取配置文件: {}", e))?;
+
+    if is_plaintext_config(&bytes) {
+        return String::from_utf8(bytes).map_err(|e| format!("配置文件不是有效的 UTF-8: {}", e));
+    }
+
+    let vault_id = crate::vaults::active_vault(app)
+        .map_err(|e| format!("无法确定当前工作区: {}", e))?
+        .id;
+    let master_key = crate::keychain::load_vault_key(app, &vault_id)
+        .map_err(|e| format!("无法获取主加密密钥: {}", e))?;
+    decrypt_config_bytes(&bytes, &master_key)
+}
+
+/// 配置文件内容是否为明文 JSON
+fn is_plaintext_config(bytes: &[u8]) -> bool {
+    serde_json::from_slice::<serde::de::IgnoredAny>(bytes).is_ok()
+}
+
+/// 用主密钥解密配置文件内容
+fn decrypt_config_bytes(bytes: &[u8], key: &[u8]) -> Result<String, String> {
+    crate::crypto::decrypt_content(bytes, key)
+        .map_err(|e| format!("无法解密配置文件: {}", e))
+}
+
+/// 编码配置文件内容：`key` 为 Some 时加密，否则原样保存为明文
+fn encode_config(content: &str, key: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    match key {
+        Some(key) => crate::crypto::encrypt_content(content, key)
+            .map_err(|e| format!("无法加密配置文件: {}", e)),
+        None => Ok(content.as_bytes().to_vec()),
+    }
+}
+
+/// 写入配置文件，`encrypt` 为 true 时用主密钥加密
+async fn write_config_file(
+    path: &std::path::Path,
+    content: &str,
+    encrypt: bool,
+    app: &AppHandle,
+) -> Result<(), String> {
+    let master_key = if encrypt {
+        Some(
+            get_or_create_master_key(app)
+                .await
+                .map_err(|e| format!("无法获取主加密密钥: {}", e))?,
+        )
+    } else {
+        None
+    };
+    let bytes = encode_config(content, master_key.as_deref())?;
+
+    tokio::fs::write(path, bytes)
+        .await
+        .map_err(|e| format!("无法写入配置文件: {}", e))
+}
+
+/// 列出可用的氛围主题
+/// 
+/// 前端调用: `invoke('list_atmosphere_themes')`
+#[tauri::command]
+pub fn list_atmosphere_themes() -> Vec<String> {
+    ATMOSPHERE_THEMES.iter().map(|theme| theme.to_string()).collect()
 }
 
+/// 可用的氛围主题（与前端 lib/themes.tsx 中的 ThemeId 保持一致）
+pub const ATMOSPHERE_THEMES: &[&str] = &["arcane", "terminal", "rusty", "vellum"];
+
+/// 目录没有配置时使用的默认氛围主题
+pub const DEFAULT_ATMOSPHERE_THEME: &str = "arcane";
+
 /// 氛围协议配置结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AtmosphereConfig {
@@ -163,10 +653,63 @@ pub struct AtmosphereConfig {
 }
 
 /// 工作区配置结构
+/// 
+/// 缺失的字段使用默认值，保证旧版本写入的 settings.json 仍可解析
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WorkspaceConfig {
     pub commit_scope: String, // "workspace" | "directory"
     pub auto_commit_interval: u64, // 分钟数
+    pub remote_name: String, // 同步使用的远程名
+    pub branch_name: Option<String>, // 同步使用的分支（None 表示自动检测默认分支）
+    pub commit_on_close: bool, // 关闭窗口时是否自动提交未提交的更改
+    pub sync_on_close: bool, // 关闭窗口时是否推送到远程（按流量计费的网络可关闭）
+    pub close_sync_timeout_secs: u64, // 关闭窗口时提交+推送的最长等待秒数，超时后强制退出
+    pub compress_notes: bool, // 加密前用 zstd 压缩笔记（旧版本客户端无法读取压缩后的文件）
+    pub commit_message_template: String, // 自动提交（定时/关闭窗口）的消息模板，支持 {timestamp} {count} {branch}
+    pub encrypt_config_files: bool, // 用主密钥加密 .vnode.json 和本配置文件，避免泄露目录结构和设置
+    pub connect_timeout_secs: u64, // 连接远程的超时秒数
+    pub network_timeout_secs: u64, // 单次 fetch/push 的最长秒数，超时后放弃（不稳定的移动网络）
+    pub decrypt_concurrency: usize, // 搜索/统计/导出时同时解密的最大文件数（移动端默认更小）
+    pub favorites: Vec<String>, // 收藏/置顶的文件（相对工作区的路径，`/` 分隔），随配置一起同步
+    pub copy_max_depth: usize, // 复制目录时允许的最大层级，超过时中止复制
+    pub copy_max_entries: usize, // 复制目录时允许的最大条目数，超过时中止复制
+    pub auto_gc_after_commits: usize, // 每累计多少次提交后在后台自动执行 GC（0 表示关闭）
+    pub plaintext_folders: Vec<String>, // 以明文保存（不加密、不带 .enc 后缀）的目录，Git pathspec 模式，例如 "Public"
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        // 根据 Sync Protocol.md，默认 10 分钟
+        WorkspaceConfig {
+            commit_scope: "workspace".to_string(),
+            auto_commit_interval: 10,
+            remote_name: "origin".to_string(),
+            branch_name: None,
+            commit_on_close: true,
+            sync_on_close: true,
+            close_sync_timeout_secs: 15,
+            compress_notes: false,
+            commit_message_template: "Auto-commit: {timestamp}".to_string(),
+            encrypt_config_files: false,
+            connect_timeout_secs: crate::git::DEFAULT_CONNECT_TIMEOUT_SECS,
+            network_timeout_secs: crate::git::DEFAULT_NETWORK_TIMEOUT_SECS,
+            decrypt_concurrency: crate::storage::DEFAULT_DECRYPT_CONCURRENCY,
+            favorites: Vec::new(),
+            copy_max_depth: crate::storage::DEFAULT_COPY_MAX_DEPTH,
+            copy_max_entries: crate::storage::DEFAULT_COPY_MAX_ENTRIES,
+            auto_gc_after_commits: crate::git::DEFAULT_AUTO_GC_AFTER_COMMITS,
+            plaintext_folders: Vec::new(),
+        }
+    }
+}
+
+/// 获取最近的日志（用于在应用内复制日志反馈问题）
+/// 
+/// 前端调用: `invoke('get_recent_logs', { maxLines: 500 })`
+#[tauri::command]
+pub fn get_recent_logs(max_lines: Option<usize>) -> Vec<String> {
+    crate::logging::recent_logs(max_lines.unwrap_or(500))
 }
 
 /// 获取平台信息
@@ -183,11 +726,18 @@ pub fn get_platform() -> Result<String, String> {
     }
 }
 
-/// 获取工作区路径
+/// 获取工作区路径（当前激活的工作区）
 /// 
 /// 前端调用: `invoke('get_workspace_path')`
 #[tauri::command]
 pub fn get_workspace_path(app: AppHandle) -> Result<String, String> {
+    crate::vaults::active_vault(&app)
+        .map(|vault| vault.path)
+        .map_err(|e| e.to_string())
+}
+
+/// 默认工作区路径（按平台约定的数据目录）
+pub(crate) fn default_workspace_path(app: &AppHandle) -> Result<String, String> {
     let platform = std::env::consts::OS;
     let base_path = match platform {
         "windows" => {
@@ -214,6 +764,67 @@ pub fn get_workspace_path(app: AppHandle) -> Result<String, String> {
     Ok(workspace_path.to_string_lossy().to_string())
 }
 
+/// 列出所有工作区及当前激活的工作区
+/// 
+/// 前端调用: `invoke('list_vaults')`
+#[tauri::command]
+pub fn list_vaults(app: AppHandle) -> Result<VaultRegistry, String> {
+    crate::vaults::list_vaults(&app)
+        .map_err(|e| e.to_string())
+}
+
+/// 注册新的工作区
+/// 
+/// 前端调用: `invoke('add_vault', { name: 'Work', path: '...' })`
+#[tauri::command]
+pub fn add_vault(name: String, path: String, app: AppHandle) -> Result<VaultEntry, String> {
+    crate::vaults::add_vault(&app, &name, &path)
+        .map_err(|e| e.to_string())
+}
+
+/// 切换激活的工作区（切换后前端应重新调用 ensure_workspace_initialized 并刷新文件树）
+/// 
+/// 前端调用: `invoke('switch_vault', { id: '...' })`
+#[tauri::command]
+pub async fn switch_vault(id: String, app: AppHandle) -> Result<VaultEntry, String> {
+    // 等待进行中的 Git 操作结束，避免同步写到切换后的工作区
+    let _repo_lock = lock_repo().await;
+    let entry = crate::vaults::switch_vault(&app, &id)
+        .map_err(|e| e.to_string())?;
+    // 明文目录等全局设置属于工作区，切换后立即换成新工作区的配置
+    load_workspace_settings(&app).await;
+    // 监听器仍指向旧工作区，由前端在切换后重新调用 start_watching
+    crate::watcher::stop_watching();
+    Ok(entry)
+}
+
+/// 开始监听当前工作区的文件变化，变化时发送 `workspace:changed` 事件
+/// 
+/// 前端调用: `invoke('start_watching', { debounceMs: 500, autoCommit: false })`
+#[tauri::command]
+pub fn start_watching(
+    debounce_ms: Option<u64>,
+    auto_commit: Option<bool>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let workspace_path = get_workspace_path(app.clone())?;
+    crate::watcher::start_watching(
+        app,
+        &workspace_path,
+        debounce_ms.unwrap_or(crate::watcher::DEFAULT_WATCH_DEBOUNCE_MS),
+        auto_commit.unwrap_or(false),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 停止监听工作区（返回之前是否在监听）
+/// 
+/// 前端调用: `invoke('stop_watching')`
+#[tauri::command]
+pub fn stop_watching() -> bool {
+    crate::watcher::stop_watching()
+}
+
 /// 确保工作区已初始化
 /// 
 /// 前端调用: `invoke('ensure_workspace_initialized')`
@@ -229,11 +840,12 @@ pub async fn ensure_workspace_initialized(app: AppHandle) -> Result<(), String>
             .map_err(|e| format!("无法创建工作区目录: {}", e))?;
     }
     
-    // 检查并初始化 Git 仓库
-    let git_dir = workspace.join(".git");
-    if !git_dir.exists() {
-        init_repository(workspace.as_path())
-            .map_err(|e| format!("无法初始化 Git 仓库: {}", e))?;
+    // 初始化 Git 仓库（可重复调用：已存在的仓库只会补全缺失的配置）
+    init_repository(workspace.as_path())
+        .map_err(|e| format!("无法初始化 Git 仓库: {}", e))?;
+    // 迁移：清除旧版本写入远程 URL 的 PAT
+    if let Err(e) = sanitize_config_credentials(workspace.as_path()) {
+        tracing::warn!("无法清除远程 URL 中的凭据: {}", e);
     }
     
     // 创建 .config 目录
@@ -250,6 +862,7 @@ pub async fn ensure_workspace_initialized(app: AppHandle) -> Result<(), String>
         let default_config = WorkspaceConfig {
             commit_scope: "workspace".to_string(),
             auto_commit_interval: 15,
+            ..Default::default()
         };
         let content = serde_json::to_string_pretty(&default_config)
             .map_err(|e| format!("无法序列化配置: {}", e))?;
@@ -266,27 +879,79 @@ pub async fn ensure_workspace_initialized(app: AppHandle) -> Result<(), String>
 /// 前端调用: `invoke('read_workspace_config')`
 #[tauri::command]
 pub async fn read_workspace_config(app: AppHandle) -> Result<WorkspaceConfig, String> {
-    let workspace_path = get_workspace_path(app)?;
+    let workspace_path = get_workspace_path(app.clone())?;
     let config_file = PathBuf::from(&workspace_path).join(".config/settings.json");
     
     if !config_file.exists() {
-        // 返回默认配置（根据 Sync Protocol.md，默认 10 分钟）
-        return Ok(WorkspaceConfig {
-            commit_scope: "workspace".to_string(),
-            auto_commit_interval: 10,
-        });
+        // 返回默认配置（根据 Sync Protocol.md，默认 10 分钟）；切换到没有配置的工作区时同样要重置全局设置
+        let config = WorkspaceConfig::default();
+        apply_global_settings(&config);
+        return Ok(config);
     }
     
-    let content = tokio::fs::read_to_string(&config_file)
-        .await
-        .map_err(|e| format!("无法读取配置文件: {}", e))?;
+    let content = read_config_file(&config_file, &app).await?;
     
     let config: WorkspaceConfig = serde_json::from_str(&content)
         .map_err(|e| format!("无法解析配置文件: {}", e))?;
+    apply_global_settings(&config);
     
     Ok(config)
 }
 
+/// 使配置中的进程全局设置生效：网络超时、解密并发数、复制上限、自动 GC 阈值和明文目录
+fn apply_global_settings(config: &WorkspaceConfig) {
+    set_network_timeouts(config.connect_timeout_secs, config.network_timeout_secs);
+    set_decrypt_concurrency(config.decrypt_concurrency);
+    set_copy_limits(config.copy_max_depth, config.copy_max_entries);
+    set_auto_gc_after_commits(config.auto_gc_after_commits);
+    set_plaintext_folders(&config.plaintext_folders);
+}
+
+/// 读取当前工作区的配置，使其中的全局设置生效（应用启动和切换工作区后调用）
+/// 
+/// 明文目录等设置原本只在前端读写配置时生效，在此之前写入明文目录的笔记会被加密保存。
+/// 同时把明文目录中遗留的加密版本转换为明文（见 `convert_plaintext_folder_notes`）
+pub(crate) async fn load_workspace_settings(app: &AppHandle) {
+    let config = read_workspace_config_or_default(app, "load_workspace_settings").await;
+    apply_global_settings(&config);
+    if !config.plaintext_folders.is_empty() {
+        convert_plaintext_leftovers(app).await;
+    }
+}
+
+/// 把明文目录中遗留的 .enc 笔记转换为明文；失败只记录警告
+/// 
+/// 只使用本机已保存的主密钥：没有密钥时跳过，不能生成新密钥
+async fn convert_plaintext_leftovers(app: &AppHandle) {
+    let result: Result<usize, String> = async {
+        let workspace_path = get_workspace_path(app.clone())?;
+        let vault_id = crate::vaults::active_vault(app)
+            .map_err(|e| format!("无法确定当前工作区: {}", e))?
+            .id;
+        let master_key = crate::keychain::load_vault_key(app, &vault_id)
+            .map_err(|e| format!("无法获取主加密密钥: {}", e))?;
+        convert_plaintext_folder_notes(std::path::Path::new(&workspace_path), &master_key)
+            .await
+            .map_err(|e| e.to_string())
+    }
+    .await;
+    match result {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("[convert_plaintext_leftovers] 已将明文目录中的 {} 篇加密笔记转换为明文", count),
+        Err(e) => tracing::warn!("[convert_plaintext_leftovers] 无法转换明文目录中的加密笔记: {}", e),
+    }
+}
+
+/// 读取工作区配置；读取失败时（例如配置文件已加密但本机没有密钥、文件损坏）记录警告并使用默认配置
+/// 
+/// 供只需要个别选项、不应因配置不可读而失败的内部调用使用；`caller` 用于日志定位
+pub(crate) async fn read_workspace_config_or_default(app: &AppHandle, caller: &str) -> WorkspaceConfig {
+    read_workspace_config(app.clone()).await.unwrap_or_else(|e| {
+        tracing::warn!("[{}] 读取工作区配置失败，使用默认配置: {}", caller, e);
+        WorkspaceConfig::default()
+    })
+}
+
 /// 写入工作区配置
 /// 
 /// 前端调用: `invoke('write_workspace_config', { config: {...} })`
@@ -295,7 +960,7 @@ pub async fn write_workspace_config(
     app: AppHandle,
     config: WorkspaceConfig,
 ) -> Result<(), String> {
-    let workspace_path = get_workspace_path(app)?;
+    let workspace_path = get_workspace_path(app.clone())?;
     let config_file = PathBuf::from(&workspace_path).join(".config/settings.json");
     
     // 确保 .config 目录存在
@@ -308,13 +973,108 @@ pub async fn write_workspace_config(
     let content = serde_json::to_string_pretty(&config)
         .map_err(|e| format!("无法序列化配置: {}", e))?;
     
-    tokio::fs::write(&config_file, content)
+    let previous_folders = read_workspace_config_or_default(&app, "write_workspace_config")
         .await
-        .map_err(|e| format!("无法写入配置文件: {}", e))?;
-    
+        .plaintext_folders;
+
+    // 是否加密由新配置本身决定；读取时会自动识别明文/密文
+    write_config_file(&config_file, &content, config.encrypt_config_files, &app).await?;
+    apply_global_settings(&config);
+    // 新设为明文的目录中已有的笔记立即转换，不等到下一次保存
+    if !config.plaintext_folders.is_empty() && config.plaintext_folders != previous_folders {
+        convert_plaintext_leftovers(&app).await;
+    }
     Ok(())
 }
 
+/// 将前端传入的路径转换为收藏列表使用的相对路径（`/` 分隔）
+fn favorite_path(app: &AppHandle, path: &str) -> Result<String, String> {
+    let workspace_path = get_workspace_path(app.clone())?;
+    repo_relative_path(std::path::Path::new(&workspace_path), path)
+}
+
+/// 把路径转换为相对于工作区（仓库）根目录的路径，路径可以已被删除
+fn repo_relative_path(workspace: &std::path::Path, path: &str) -> Result<String, String> {
+    let resolved = confine_to_workspace(workspace, std::path::Path::new(path))
+        .map_err(|e| e.to_string())?;
+    let root = workspace
+        .canonicalize()
+        .map_err(|e| format!("无法解析工作区路径: {}", e))?;
+    Ok(workspace_relative_path(&root, &resolved))
+}
+
+/// 文件重命名/移动后更新收藏列表；失败只记录警告，不影响重命名本身
+async fn update_favorites_after_rename(app: &AppHandle, old_path: &str, new_path: &str) {
+    let result: Result<(), String> = async {
+        let old_relative = favorite_path(app, old_path)?;
+        let new_relative = favorite_path(app, new_path)?;
+        let mut config = read_workspace_config(app.clone()).await?;
+        if rename_paths_in_list(&mut config.favorites, &old_relative, &new_relative) {
+            write_workspace_config(app.clone(), config).await?;
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("[favorites] 重命名后更新收藏失败: {}", e);
+    }
+}
+
+/// 文件被移出当前工作区后从收藏中移除；失败只记录警告，不影响移动本身
+async fn remove_favorite_after_move(app: &AppHandle, path: &str) {
+    let result: Result<(), String> = async {
+        let relative = favorite_path(app, path)?;
+        let mut config = read_workspace_config(app.clone()).await?;
+        let before = config.favorites.len();
+        config.favorites.retain(|favorite| favorite != &relative);
+        if config.favorites.len() != before {
+            write_workspace_config(app.clone(), config).await?;
+        }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("[favorites] 移动到其他工作区后更新收藏失败: {}", e);
+    }
+}
+
+/// 收藏文件（重复收藏不会产生重复条目）
+/// 
+/// 前端调用: `invoke('add_favorite', { path: '...' })`
+#[tauri::command]
+pub async fn add_favorite(path: String, app: AppHandle) -> Result<Vec<String>, String> {
+    let relative = favorite_path(&app, &path)?;
+    let mut config = read_workspace_config(app.clone()).await?;
+    if !config.favorites.contains(&relative) {
+        config.favorites.push(relative);
+        write_workspace_config(app, config.clone()).await?;
+    }
+    Ok(config.favorites)
+}
+
+/// 取消收藏文件
+/// 
+/// 前端调用: `invoke('remove_favorite', { path: '...' })`
+#[tauri::command]
+pub async fn remove_favorite(path: String, app: AppHandle) -> Result<Vec<String>, String> {
+    let relative = favorite_path(&app, &path)?;
+    let mut config = read_workspace_config(app.clone()).await?;
+    let before = config.favorites.len();
+    config.favorites.retain(|favorite| favorite != &relative);
+    if config.favorites.len() != before {
+        write_workspace_config(app, config.clone()).await?;
+    }
+    Ok(config.favorites)
+}
+
+/// 列出收藏的文件（相对工作区的路径，按收藏顺序）
+/// 
+/// 前端调用: `invoke('list_favorites')`
+#[tauri::command]
+pub async fn list_favorites(app: AppHandle) -> Result<Vec<String>, String> {
+    Ok(read_workspace_config(app).await?.favorites)
+}
+
 /// 创建新文件
 /// 
 /// 前端调用: `invoke('create_file', { path: '...', content: '...' })`
@@ -324,16 +1084,41 @@ pub async fn create_file_command(
     content: String,
     app: AppHandle,
 ) -> Result<(), String> {
-    create_file(&path, &content, &app)
+    let plaintext = in_plaintext_folder(&app, &path)?;
+    create_file(&path, &content, plaintext, &app)
         .await
         .map_err(|e| e.to_string())
 }
 
+/// 用 `templates/` 目录下的模板创建新文件（自动填入 `{{date}}`、`{{title}}` 等变量）
+/// 
+/// 前端调用: `invoke('create_file_from_template_command', { destPath: '...', templateName: 'daily', variables: { project: '...' } })`
+#[tauri::command]
+pub async fn create_file_from_template_command(
+    dest_path: String,
+    template_name: String,
+    variables: Option<std::collections::HashMap<String, String>>,
+    app: AppHandle,
+) -> Result<(), String> {
+    ensure_in_workspace(&app, &dest_path)?;
+    let workspace_path = get_workspace_path(app.clone())?;
+    create_file_from_template(
+        PathBuf::from(workspace_path).as_path(),
+        &dest_path,
+        &template_name,
+        &variables.unwrap_or_default(),
+        &app,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
 /// 创建新目录
 /// 
 /// 前端调用: `invoke('create_directory', { path: '...' })`
 #[tauri::command]
-pub async fn create_directory_command(path: String) -> Result<(), String> {
+pub async fn create_directory_command(path: String, app: AppHandle) -> Result<(), String> {
+    ensure_in_workspace(&app, &path)?;
     create_directory(&path).await.map_err(|e| e.to_string())
 }
 
@@ -341,15 +1126,42 @@ pub async fn create_directory_command(path: String) -> Result<(), String> {
 /// 
 /// 前端调用: `invoke('delete_file', { path: '...' })`
 #[tauri::command]
-pub async fn delete_file_command(path: String) -> Result<(), String> {
+pub async fn delete_file_command(path: String, app: AppHandle) -> Result<(), String> {
+    ensure_in_workspace(&app, &path)?;
     delete_file(&path).await.map_err(|e| e.to_string())
 }
 
+/// 删除文件，并清理因此变空的父目录
+/// 
+/// 前端调用: `invoke('delete_file_and_prune_command', { path: '...' })`
+#[tauri::command]
+pub async fn delete_file_and_prune_command(path: String, app: AppHandle) -> Result<usize, String> {
+    ensure_in_workspace(&app, &path)?;
+    let workspace_path = get_workspace_path(app.clone())?;
+    delete_file_and_prune(&path, PathBuf::from(workspace_path).as_path())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 拒绝把工作区根目录当作普通目录删除
+fn reject_workspace_root(path: &str, workspace_path: &str) -> Result<(), String> {
+    let canonical = |p: &str| std::fs::canonicalize(p).unwrap_or_else(|_| PathBuf::from(p));
+    if canonical(path) == canonical(workspace_path) {
+        return Err(format!("拒绝删除工作区根目录: {}", path));
+    }
+    Ok(())
+}
+
 /// 删除目录
 /// 
+/// 工作区根目录和 .git 目录会被拒绝
+/// 
 /// 前端调用: `invoke('delete_directory', { path: '...' })`
 #[tauri::command]
-pub async fn delete_directory_command(path: String) -> Result<(), String> {
+pub async fn delete_directory_command(path: String, app: AppHandle) -> Result<(), String> {
+    ensure_in_workspace(&app, &path)?;
+    let workspace_path = get_workspace_path(app)?;
+    reject_workspace_root(&path, &workspace_path)?;
     delete_directory(&path).await.map_err(|e| e.to_string())
 }
 
@@ -357,7 +1169,7 @@ pub async fn delete_directory_command(path: String) -> Result<(), String> {
 /// 
 /// 此命令会：
 /// 1. 执行文件删除
-/// 2. 执行 git add -A（自动处理删除）
+/// 2. 只从索引中移除被删除的路径（不扫描整个工作区）
 /// 3. 执行 git commit
 /// 4. 执行 git push（如果配置了远程仓库和 PAT）
 /// 
@@ -369,50 +1181,52 @@ pub async fn delete_file_with_git_sync_command(
     remote_name: String,
     branch_name: String,
     pat_token: Option<String>,
-    _app: AppHandle,
+    app: AppHandle,
 ) -> Result<(), String> {
-    use crate::git::commit_changes;
     use std::path::Path;
     
     let repo_path = Path::new(&workspace_path);
+    ensure_in_workspace(&app, &path)?;
     
     // 步骤 1: 执行文件删除
-    eprintln!("[delete_file_with_git_sync] 步骤 1: 执行文件删除");
+    tracing::info!("[delete_file_with_git_sync] 步骤 1: 执行文件删除");
     delete_file(&path)
         .await
         .map_err(|e| format!("删除失败: {}", e))?;
     
-    // 步骤 2: 使用 git2-rs API 更新索引（自动处理删除）
-    // 索引更新将在 commit_changes 中自动处理
-    eprintln!("[delete_file_with_git_sync] 步骤 2: 使用 git2-rs API 更新索引（在 commit 中处理）");
-    
-    // 步骤 3: 执行 git commit（commit_changes 会自动处理索引更新）
-    eprintln!("[delete_file_with_git_sync] 步骤 3: 执行 git commit");
+    // 步骤 2 和 3: 从索引中移除被删除的路径并提交（remove_from_index）
+    tracing::info!("[delete_file_with_git_sync] 步骤 2-3: 从索引移除并提交");
+    let _repo_lock = lock_repo().await;
     let commit_message = format!("delete: {}", path);
-    commit_changes(repo_path, &commit_message)
+    let relative = repo_relative_path(repo_path, &path)?;
+    let commit = remove_from_index(repo_path, &[relative], &commit_message, "main")
         .map_err(|e| format!("git commit 失败: {}", e))?;
+    if commit.created {
+        events::emit_commit_done(&app, &commit.sha, &commit_message);
+    }
     
-    // 步骤 4: 如果配置了远程仓库和 PAT，执行完整同步（包含 squash 和 push）
+    // 步骤 4: 如果配置了远程仓库和 PAT，推送到远程
     // 重要：本地删除 + commit 成功后，应视为"删除成功"（Local-first）。
     // 同步失败不应回滚/不应让前端认为删除失败，否则会造成 UI 与文件系统状态不一致。
     // 
     // 注意：删除/重命名操作后立即同步可能导致 fast-forward 覆盖工作区。
     // 因此这里只执行 push（如果本地领先），不执行 fetch/rebase，避免覆盖刚删除/重命名的文件。
     if let Some(ref token) = pat_token {
-        eprintln!("[delete_file_with_git_sync] 步骤 4: 尝试 push（不执行 fetch/rebase，避免覆盖工作区）");
+        tracing::info!("[delete_file_with_git_sync] 步骤 4: 尝试 push（不执行 fetch/rebase，避免覆盖工作区）");
         // 只 push，不 fetch/rebase，避免 fast-forward 覆盖刚删除的文件
         match crate::git::push_to_remote(repo_path, &remote_name, &branch_name, Some(token.as_str())) {
             Ok(_) => {
-                eprintln!("[delete_file_with_git_sync] push 成功");
+                tracing::info!("[delete_file_with_git_sync] push 成功");
+                events::emit_push_done(&app, &remote_name, &branch_name);
         }
             Err(e) => {
-                eprintln!("[delete_file_with_git_sync] 警告：push 失败（不影响本地删除完成）: {}", e);
-                eprintln!("[delete_file_with_git_sync] 建议：稍后手动同步或下次启动自动同步");
+                tracing::warn!("[delete_file_with_git_sync] 警告：push 失败（不影响本地删除完成）: {}", e);
+                tracing::info!("[delete_file_with_git_sync] 建议：稍后手动同步或下次启动自动同步");
             }
         }
     }
     
-    eprintln!("[delete_file_with_git_sync] 完成：删除和 Git 同步成功");
+    tracing::info!("[delete_file_with_git_sync] 完成：删除和 Git 同步成功");
     Ok(())
 }
 
@@ -420,7 +1234,7 @@ pub async fn delete_file_with_git_sync_command(
 /// 
 /// 此命令会：
 /// 1. 执行目录删除
-/// 2. 执行 git add -A（自动处理删除）
+/// 2. 只从索引中移除被删除的路径（不扫描整个工作区）
 /// 3. 执行 git commit
 /// 4. 执行 git push（如果配置了远程仓库和 PAT）
 /// 
@@ -432,51 +1246,53 @@ pub async fn delete_directory_with_git_sync_command(
     remote_name: String,
     branch_name: String,
     pat_token: Option<String>,
-    _app: AppHandle,
+    app: AppHandle,
 ) -> Result<(), String> {
-    use crate::git::commit_changes;
     use std::path::Path;
     
     let repo_path = Path::new(&workspace_path);
+    ensure_in_workspace(&app, &path)?;
     
     // 步骤 1: 执行目录删除
-    eprintln!("[delete_directory_with_git_sync] 步骤 1: 执行目录删除");
+    tracing::info!("[delete_directory_with_git_sync] 步骤 1: 执行目录删除");
+    reject_workspace_root(&path, &workspace_path)?;
     delete_directory(&path)
         .await
         .map_err(|e| format!("删除失败: {}", e))?;
     
-    // 步骤 2: 执行 git add -A（自动处理删除）
-    // 步骤 2: 使用 git2-rs API 更新索引（自动处理删除）
-    // 索引更新将在 commit_changes 中自动处理
-    eprintln!("[delete_directory_with_git_sync] 步骤 2: 使用 git2-rs API 更新索引（在 commit 中处理）");
-    
-    // 步骤 3: 执行 git commit（commit_changes 会自动处理索引更新）
-    eprintln!("[delete_directory_with_git_sync] 步骤 3: 执行 git commit");
+    // 步骤 2 和 3: 从索引中移除被删除的路径并提交（remove_from_index）
+    tracing::info!("[delete_directory_with_git_sync] 步骤 2-3: 从索引移除并提交");
+    let _repo_lock = lock_repo().await;
     let commit_message = format!("delete: {}", path);
-    commit_changes(repo_path, &commit_message)
+    let relative = repo_relative_path(repo_path, &path)?;
+    let commit = remove_from_index(repo_path, &[relative], &commit_message, "main")
         .map_err(|e| format!("git commit 失败: {}", e))?;
+    if commit.created {
+        events::emit_commit_done(&app, &commit.sha, &commit_message);
+    }
     
-    // 步骤 4: 如果配置了远程仓库和 PAT，执行完整同步（包含 squash 和 push）
+    // 步骤 4: 如果配置了远程仓库和 PAT，推送到远程
     // 重要：本地删除 + commit 成功后，应视为"删除成功"（Local-first）。
     // 同步失败不应回滚/不应让前端认为删除失败，否则会造成 UI 与文件系统状态不一致。
     //
     // 注意：删除/重命名操作后立即同步可能导致 fast-forward 覆盖工作区。
     // 因此这里只执行 push（如果本地领先），不执行 fetch/rebase，避免覆盖刚删除/重命名的文件。
     if let Some(ref token) = pat_token {
-        eprintln!("[delete_directory_with_git_sync] 步骤 4: 尝试 push（不执行 fetch/rebase，避免覆盖工作区）");
+        tracing::info!("[delete_directory_with_git_sync] 步骤 4: 尝试 push（不执行 fetch/rebase，避免覆盖工作区）");
         // 只 push，不 fetch/rebase，避免 fast-forward 覆盖刚删除的目录
         match crate::git::push_to_remote(repo_path, &remote_name, &branch_name, Some(token.as_str())) {
             Ok(_) => {
-                eprintln!("[delete_directory_with_git_sync] push 成功");
+                tracing::info!("[delete_directory_with_git_sync] push 成功");
+                events::emit_push_done(&app, &remote_name, &branch_name);
         }
             Err(e) => {
-                eprintln!("[delete_directory_with_git_sync] 警告：push 失败（不影响本地删除完成）: {}", e);
-                eprintln!("[delete_directory_with_git_sync] 建议：稍后手动同步或下次启动自动同步");
+                tracing::warn!("[delete_directory_with_git_sync] 警告：push 失败（不影响本地删除完成）: {}", e);
+                tracing::info!("[delete_directory_with_git_sync] 建议：稍后手动同步或下次启动自动同步");
             }
         }
     }
     
-    eprintln!("[delete_directory_with_git_sync] 完成：删除和 Git 同步成功");
+    tracing::info!("[delete_directory_with_git_sync] 完成：删除和 Git 同步成功");
     Ok(())
 }
 
@@ -487,10 +1303,15 @@ pub async fn delete_directory_with_git_sync_command(
 pub async fn rename_file_or_directory_command(
     old_path: String,
     new_path: String,
+    app: AppHandle,
 ) -> Result<(), String> {
+    ensure_in_workspace(&app, &old_path)?;
+    ensure_in_workspace(&app, &new_path)?;
     rename_file_or_directory(&old_path, &new_path)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    update_favorites_after_rename(&app, &old_path, &new_path).await;
+    Ok(())
 }
 
 /// 重命名文件或目录并同步到 Git（原子操作）
@@ -510,53 +1331,167 @@ pub async fn rename_file_with_git_sync_command(
     remote_name: String,
     branch_name: String,
     pat_token: Option<String>,
-    _app: AppHandle,
+    app: AppHandle,
 ) -> Result<(), String> {
-    use crate::git::commit_changes;
     use std::path::Path;
     
     let repo_path = Path::new(&workspace_path);
+    ensure_in_workspace(&app, &old_path)?;
+    ensure_in_workspace(&app, &new_path)?;
     
     // 步骤 1: 执行重命名
-    eprintln!("[rename_file_with_git_sync] 步骤 1: 执行文件重命名");
+    tracing::info!("[rename_file_with_git_sync] 步骤 1: 执行文件重命名");
     rename_file_or_directory(&old_path, &new_path)
         .await
         .map_err(|e| format!("重命名失败: {}", e))?;
+    // 收藏列表随配置一起提交
+    update_favorites_after_rename(&app, &old_path, &new_path).await;
     
     // 步骤 2: 使用 git2-rs API 更新索引（自动处理删除旧索引、添加新索引）
-    eprintln!("[rename_file_with_git_sync] 步骤 2: 使用 git2-rs API 更新索引");
+    tracing::info!("[rename_file_with_git_sync] 步骤 2: 使用 git2-rs API 更新索引");
     // 索引更新将在 commit_changes 中自动处理
     
     // 步骤 3: 执行 git commit
-    eprintln!("[rename_file_with_git_sync] 步骤 3: 执行 git commit");
+    tracing::info!("[rename_file_with_git_sync] 步骤 3: 执行 git commit");
+    let _repo_lock = lock_repo().await;
     let commit_message = format!("rename: {} -> {}", old_path, new_path);
-    commit_changes(repo_path, &commit_message)
+    let commit = commit_changes_if_changed(repo_path, &commit_message, "main")
         .map_err(|e| format!("git commit 失败: {}", e))?;
+    if commit.created {
+        events::emit_commit_done(&app, &commit.sha, &commit_message);
+    }
     
-    // 步骤 4: 如果配置了远程仓库和 PAT，执行完整同步（包含 squash 和 push）
+    // 步骤 4: 如果配置了远程仓库和 PAT，推送到远程
     // 重要：本地重命名 + commit 成功后，应视为"重命名成功"（Local-first）。
     // 同步失败不应回滚/不应让前端认为重命名失败，否则会出现"文件已改名但 UI 仍认为失败 -> 下次用旧路径报不存在"。
     //
     // 注意：删除/重命名操作后立即同步可能导致 fast-forward 覆盖工作区。
     // 因此这里只执行 push（如果本地领先），不执行 fetch/rebase，避免覆盖刚删除/重命名的文件。
     if let Some(ref token) = pat_token {
-        eprintln!("[rename_file_with_git_sync] 步骤 4: 尝试 push（不执行 fetch/rebase，避免覆盖工作区）");
+        tracing::info!("[rename_file_with_git_sync] 步骤 4: 尝试 push（不执行 fetch/rebase，避免覆盖工作区）");
         // 只 push，不 fetch/rebase，避免 fast-forward 覆盖刚重命名的文件
         match crate::git::push_to_remote(repo_path, &remote_name, &branch_name, Some(token.as_str())) {
             Ok(_) => {
-                eprintln!("[rename_file_with_git_sync] push 成功");
+                tracing::info!("[rename_file_with_git_sync] push 成功");
+                events::emit_push_done(&app, &remote_name, &branch_name);
         }
             Err(e) => {
-                eprintln!("[rename_file_with_git_sync] 警告：push 失败（不影响本地重命名完成）: {}", e);
-                eprintln!("[rename_file_with_git_sync] 建议：刷新文件列表并稍后手动同步");
+                tracing::warn!("[rename_file_with_git_sync] 警告：push 失败（不影响本地重命名完成）: {}", e);
+                tracing::info!("[rename_file_with_git_sync] 建议：刷新文件列表并稍后手动同步");
             }
         }
     }
     
-    eprintln!("[rename_file_with_git_sync] 完成：重命名和 Git 同步成功");
+    tracing::info!("[rename_file_with_git_sync] 完成：重命名和 Git 同步成功");
     Ok(())
 }
 
+/// 检查整库文件能否用当前主密钥解密
+/// 
+/// 前端调用: `invoke('scan_vault_integrity_command', { workspacePath: '...' })`
+#[tauri::command]
+pub async fn scan_vault_integrity_command(
+    workspace_path: String,
+    app: AppHandle,
+) -> Result<VaultIntegrityReport, String> {
+    scan_vault_integrity(&workspace_path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 用当前主密钥和加密格式重新加密单个文件（修复完整性检查发现的旧格式文件）
+/// 
+/// 前端调用: `invoke('rekey_file_command', { workspacePath: '...', relativePath: '...' })`
+#[tauri::command]
+pub async fn rekey_file_command(
+    workspace_path: String,
+    relative_path: String,
+    app: AppHandle,
+) -> Result<(), String> {
+    rekey_file(&workspace_path, &relative_path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 获取整库统计信息（笔记数、总字数、总大小、最近编辑的笔记）
+/// 
+/// 前端调用: `invoke('get_vault_stats_command', { workspacePath: '...' })`
+#[tauri::command]
+pub async fn get_vault_stats_command(workspace_path: String, app: AppHandle) -> Result<VaultStats, String> {
+    get_vault_stats(&workspace_path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 查询路径所在卷的剩余可用空间（字节），用于在导入、拉取等大操作前提示用户
+/// 
+/// 前端调用: `invoke('get_available_space_command', { path: '...' })`
+#[tauri::command]
+pub fn get_available_space_command(path: String) -> Result<u64, String> {
+    get_available_space(std::path::Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// 查找内容相同的重复笔记
+/// 
+/// 前端调用: `invoke('find_duplicates_command', { workspacePath: '...' })`
+#[tauri::command]
+pub async fn find_duplicates_command(
+    workspace_path: String,
+    app: AppHandle,
+) -> Result<Vec<DuplicateGroup>, String> {
+    find_duplicates(&workspace_path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 建立标签索引（标签 -> 笔记路径）
+/// 
+/// 前端调用: `invoke('index_tags_command', { workspacePath: '...' })`
+#[tauri::command]
+pub async fn index_tags_command(workspace_path: String, app: AppHandle) -> Result<TagIndex, String> {
+    index_tags(&workspace_path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 建立反向链接索引（笔记 -> 链接到它的笔记）
+/// 
+/// 前端调用: `invoke('build_backlinks_command', { workspacePath: '...' })`
+#[tauri::command]
+pub async fn build_backlinks_command(workspace_path: String, app: AppHandle) -> Result<BacklinkIndex, String> {
+    build_backlinks(&workspace_path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 重命名笔记，改写全库中指向它的 `[[链接]]` 并提交
+/// 
+/// 前端调用: `invoke('rename_with_link_update_command', { workspacePath: '...', oldPath: '...', newPath: '...' })`
+#[tauri::command]
+pub async fn rename_with_link_update_command(
+    workspace_path: String,
+    old_path: String,
+    new_path: String,
+    app: AppHandle,
+) -> Result<usize, String> {
+    ensure_in_workspace(&app, &old_path)?;
+    ensure_in_workspace(&app, &new_path)?;
+
+    let updated = rename_with_link_update(&workspace_path, &old_path, &new_path, &app)
+        .await
+        .map_err(|e| e.to_string())?;
+    update_favorites_after_rename(&app, &old_path, &new_path).await;
+
+    let _repo_lock = lock_repo().await;
+    let commit_message = format!("rename: {} -> {} ({} links updated)", old_path, new_path, updated);
+    let commit = commit_changes_if_changed(PathBuf::from(&workspace_path).as_path(), &commit_message, "main")
+        .map_err(|e| format!("git commit 失败: {}", e))?;
+    if commit.created {
+        events::emit_commit_done(&app, &commit.sha, &commit_message);
+    }
+    Ok(updated)
+}
+
 /// 复制文件或目录
 /// 
 /// 前端调用: `invoke('copy_file_or_directory', { sourcePath: '...', destPath: '...' })`
@@ -564,7 +1499,10 @@ pub async fn rename_file_with_git_sync_command(
 pub async fn copy_file_or_directory_command(
     source_path: String,
     dest_path: String,
+    app: AppHandle,
 ) -> Result<(), String> {
+    ensure_in_workspace(&app, &source_path)?;
+    ensure_in_workspace(&app, &dest_path)?;
     copy_file_or_directory(&source_path, &dest_path)
         .await
         .map_err(|e| e.to_string())
@@ -577,18 +1515,126 @@ pub async fn copy_file_or_directory_command(
 pub async fn move_file_or_directory_command(
     source_path: String,
     dest_path: String,
+    app: AppHandle,
 ) -> Result<(), String> {
+    ensure_in_workspace(&app, &source_path)?;
+    ensure_in_workspace(&app, &dest_path)?;
     move_file_or_directory(&source_path, &dest_path)
         .await
+        .map_err(|e| e.to_string())?;
+    update_favorites_after_rename(&app, &source_path, &dest_path).await;
+    Ok(())
+}
+
+/// 将笔记移动到另一个工作区（用目标工作区的主密钥重新加密），并在两个仓库中分别提交
+/// 
+/// 前端调用: `invoke('move_between_vaults_command', { sourceVault: '...', sourcePath: '...', destVault: '...', destPath: '...' })`
+#[tauri::command]
+pub async fn move_between_vaults_command(
+    source_vault: String,
+    source_path: String,
+    dest_vault: String,
+    dest_path: String,
+    app: AppHandle,
+) -> Result<(), String> {
+    let source = crate::vaults::find_vault(&app, &source_vault).map_err(|e| e.to_string())?;
+    let dest = crate::vaults::find_vault(&app, &dest_vault).map_err(|e| e.to_string())?;
+    let moved = move_between_vaults(&app, &source, &source_path, &dest, &dest_path)
+        .await
+        .map_err(|e| e.to_string())?;
+    if crate::vaults::active_vault(&app).is_ok_and(|active| active.id == source.id) {
+        remove_favorite_after_move(&app, &moved.to_string_lossy()).await;
+    }
+
+    let _repo_lock = lock_repo().await;
+    for (vault, message) in [
+        (&source, format!("move to vault {}: {}", dest.name, source_path)),
+        (&dest, format!("move from vault {}: {}", source.name, dest_path)),
+    ] {
+        let commit = commit_changes_if_changed(PathBuf::from(&vault.path).as_path(), &message, "main")
+            .map_err(|e| format!("工作区 {} git commit 失败: {}", vault.name, e))?;
+        if commit.created {
+            events::emit_commit_done(&app, &commit.sha, &message);
+        }
+    }
+    Ok(())
+}
+
+/// 重置当前工作区的主密钥（仅在存储的密钥损坏、读取笔记报错时使用）
+/// 
+/// 用旧密钥加密的文件将无法再解密；原密钥条目会备份在 vault_keys.json 中
+/// 
+/// 前端调用: `invoke('reset_master_key_command')`
+#[tauri::command]
+pub fn reset_master_key_command(app: AppHandle) -> Result<(), String> {
+    crate::keychain::reset_master_key(&app)
         .map_err(|e| e.to_string())
 }
 
+/// 导出当前工作区的主密钥恢复码（用口令保护，建议离线保存）
+/// 
+/// 前端调用: `invoke('export_master_key_command', { passphrase: '...' })`
+#[tauri::command]
+pub fn export_master_key_command(app: AppHandle, passphrase: String) -> Result<String, String> {
+    crate::keychain::export_master_key(&app, &passphrase)
+        .map_err(|e| e.to_string())
+}
+
+/// 从恢复码恢复当前工作区的主密钥
+/// 
+/// 前端调用: `invoke('import_master_key_command', { blob: '...', passphrase: '...' })`
+#[tauri::command]
+pub fn import_master_key_command(app: AppHandle, blob: String, passphrase: String) -> Result<(), String> {
+    crate::keychain::import_master_key(&app, &blob, &passphrase)
+        .map_err(|e| e.to_string())
+}
+
+/// 检查当前存储的主密钥能否解密工作区中的文件（抽样试解密）
+/// 
+/// 前端调用: `invoke('can_decrypt_vault_command', { workspacePath: '...' })`
+#[tauri::command]
+pub fn can_decrypt_vault_command(workspace_path: String, app: AppHandle) -> Result<bool, String> {
+    let master_key = crate::keychain::get_or_create_master_key_sync(&app)
+        .map_err(|e| e.to_string())?;
+    Ok(crate::storage::can_decrypt_vault(PathBuf::from(workspace_path).as_path(), &master_key))
+}
+
 /// 存储 GitHub PAT Token
 /// 
+/// 保存后检查令牌对当前远程是否有推送权限；有问题时返回警告（不影响保存）
+/// 
 /// 前端调用: `invoke('store_pat', { token: '...' })`
 #[tauri::command]
-pub async fn store_pat(app: AppHandle, token: String) -> Result<(), String> {
+pub async fn store_pat(app: AppHandle, token: String) -> Result<Option<String>, String> {
     store_pat_token(&app, &token)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let remote_name = read_workspace_config_or_default(&app, "store_pat")
+        .await
+        .remote_name;
+    let remote_url = get_workspace_path(app)
+        .ok()
+        .and_then(|workspace| get_remote_url(workspace, remote_name).ok().flatten());
+    let Some(remote_url) = remote_url else {
+        return Ok(None);
+    };
+
+    match crate::github::check_pat_scopes(&token, &remote_url).await {
+        Ok(check) => Ok(check.warning.filter(|_| check.can_push.is_some())),
+        Err(e) => {
+            tracing::warn!("[PAT] 无法检查 PAT 权限: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// 检查 PAT 是否有效、对远程仓库是否有推送权限（仅支持 github.com）
+/// 
+/// 前端调用: `invoke('check_pat_scopes_command', { pat: '...', remoteUrl: 'https://github.com/...' })`
+#[tauri::command]
+pub async fn check_pat_scopes_command(pat: String, remote_url: String) -> Result<PatScopeCheck, String> {
+    crate::github::check_pat_scopes(&pat, &remote_url)
         .await
         .map_err(|e| e.to_string())
 }
@@ -623,11 +1669,49 @@ pub async fn has_pat(app: AppHandle) -> Result<bool, String> {
         .map_err(|e| e.to_string())
 }
 
+/// 查找当前工作区中远程仓库的 URL（按远程区分存储 PAT 时使用）
+fn workspace_remote_url(app: &AppHandle, remote_name: &str) -> Result<String, String> {
+    let workspace_path = get_workspace_path(app.clone())?;
+    get_remote_url(workspace_path, remote_name.to_string())?
+        .ok_or_else(|| format!("未配置远程仓库: {}", remote_name))
+}
+
+/// 为指定远程存储 PAT（按远程主机区分，多个远程可使用不同的令牌）
+/// 
+/// 前端调用: `invoke('store_pat_for', { remoteName: 'backup', token: '...' })`
+#[tauri::command]
+pub fn store_pat_for(app: AppHandle, remote_name: String, token: String) -> Result<(), String> {
+    let remote_url = workspace_remote_url(&app, &remote_name)?;
+    crate::keychain::store_pat_for(&app, &remote_url, &token)
+        .map_err(|e| e.to_string())
+}
+
+/// 获取指定远程使用的 PAT（未单独配置时返回全局 PAT）
+/// 
+/// 前端调用: `invoke('get_pat_for', { remoteName: 'backup' })`
+#[tauri::command]
+pub fn get_pat_for(app: AppHandle, remote_name: String) -> Result<Option<String>, String> {
+    let remote_url = workspace_remote_url(&app, &remote_name)?;
+    crate::keychain::get_pat_for(&app, &remote_url)
+        .map_err(|e| e.to_string())
+}
+
+/// 删除为指定远程单独存储的 PAT
+/// 
+/// 前端调用: `invoke('remove_pat_for', { remoteName: 'backup' })`
+#[tauri::command]
+pub fn remove_pat_for(app: AppHandle, remote_name: String) -> Result<(), String> {
+    let remote_url = workspace_remote_url(&app, &remote_name)?;
+    crate::keychain::remove_pat_for(&app, &remote_url)
+        .map_err(|e| e.to_string())
+}
+
 /// 添加远程仓库
 /// 
 /// 前端调用: `invoke('add_remote', { path: '...', name: 'origin', url: '...' })`
 #[tauri::command]
 pub fn add_remote(path: String, name: String, url: String) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
     crate::git::add_remote(PathBuf::from(path).as_path(), &name, &url)
         .map_err(|e| e.to_string())
 }
@@ -641,39 +1725,188 @@ pub fn get_remote_url(path: String, name: String) -> Result<Option<String>, Stri
         .map_err(|e| e.to_string())
 }
 
+/// 获取远程的 PAT 认证格式（未配置时 GitLab 主机为 oauth2，其余为 token）
+/// 
+/// 前端调用: `invoke('get_remote_auth_format_command', { path: '...', name: 'origin' })`
+#[tauri::command]
+pub fn get_remote_auth_format_command(path: String, name: String) -> Result<PatAuthFormat, String> {
+    get_remote_auth_format(PathBuf::from(path).as_path(), &name)
+        .map_err(|e| e.to_string())
+}
+
+/// 设置远程的 PAT 认证格式（自建 GitLab/Gitea 等服务）
+/// 
+/// 前端调用: `invoke('set_remote_auth_format_command', { path: '...', name: 'origin', format: { kind: 'basic', username: '...' } })`
+#[tauri::command]
+pub fn set_remote_auth_format_command(path: String, name: String, format: PatAuthFormat) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
+    set_remote_auth_format(PathBuf::from(path).as_path(), &name, &format)
+        .map_err(|e| e.to_string())
+}
+
+/// 清除所有远程 URL 中嵌入的 PAT（旧版本把令牌写入了 .git/config）
+/// 
+/// 前端调用: `invoke('sanitize_config_credentials_command', { path: '...' })`
+#[tauri::command]
+pub fn sanitize_config_credentials_command(path: String) -> Result<usize, String> {
+    let _repo_lock = lock_repo_blocking();
+    sanitize_config_credentials(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 读取 Git 配置项（未设置时返回 null）
+/// 
+/// 前端调用: `invoke('get_git_config_command', { path: '...', key: 'http.postBuffer' })`
+#[tauri::command]
+pub fn get_git_config_command(path: String, key: String) -> Result<Option<String>, String> {
+    get_git_config(PathBuf::from(path).as_path(), &key)
+        .map_err(|e| e.to_string())
+}
+
+/// 在仓库配置中设置 Git 配置项（例如大仓库首次推送失败时调大 `http.postBuffer`）
+/// 
+/// 前端调用: `invoke('set_git_config_command', { path: '...', key: 'http.postBuffer', value: '524288000' })`
+#[tauri::command]
+pub fn set_git_config_command(path: String, key: String, value: String) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
+    set_git_config(PathBuf::from(path).as_path(), &key, &value)
+        .map_err(|e| e.to_string())
+}
+
 /// 删除远程仓库配置
 /// 
 /// 前端调用: `invoke('remove_remote', { path: '...', name: 'origin' })`
 #[tauri::command]
 pub fn remove_remote(path: String, name: String) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
     crate::git::remove_remote(PathBuf::from(path).as_path(), &name)
         .map_err(|e| e.to_string())
 }
 
+/// 获取远程分支的最新提交（只列出引用，不下载对象），用于“有更新”提示
+/// 
+/// 前端调用: `invoke('remote_head_command', { path: '...', remoteName: 'origin', branchName: 'main', patToken: '...' })`
+#[tauri::command]
+pub fn remote_head_command(
+    path: String,
+    remote_name: String,
+    branch_name: String,
+    pat_token: Option<String>,
+) -> Result<Option<String>, String> {
+    // 只读取远程引用，不修改本地仓库，无需仓库锁
+    remote_head(PathBuf::from(path).as_path(), &remote_name, &branch_name, pat_token.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// 预估同步需要上传/下载的数据量（不下载对象），用于移动网络下提示用户
+/// 
+/// 远程有本地没有的提交时，若远程是 GitHub，则通过 API 列出远程文件树，按本地缺少的文件估算下载量
+/// 
+/// 前端调用: `invoke('estimate_sync_size_command', { path: '...', remoteName: 'origin', branchName: 'main', patToken: '...' })`
+#[tauri::command]
+pub async fn estimate_sync_size_command(
+    path: String,
+    remote_name: String,
+    branch_name: String,
+    pat_token: Option<String>,
+) -> Result<SyncSizeEstimate, String> {
+    // 只读取远程引用和本地对象，不修改仓库，无需仓库锁；
+    // 访问网络和打包对象都是阻塞调用，放到阻塞线程池中执行
+    let repo_path = PathBuf::from(path);
+    let mut estimate = {
+        let repo_path = repo_path.clone();
+        let remote_name = remote_name.clone();
+        let pat_token = pat_token.clone();
+        crate::locks::spawn_blocking(move || {
+            estimate_sync_size(&repo_path, &remote_name, &branch_name, pat_token.as_deref())
+        })
+        .await
+        .map_err(|e| format!("预估同步数据量异常终止: {}", e))?
+        .map_err(|e| e.to_string())?
+    };
+    if estimate.download_known {
+        return Ok(estimate);
+    }
+
+    let remote_url = crate::git::get_remote_url(&repo_path, &remote_name)
+        .map_err(|e| e.to_string())?;
+    if let (Some(head), Some(url)) = (estimate.remote_head.clone(), remote_url) {
+        match crate::github::list_tree_blobs(pat_token.as_deref(), &url, &head).await {
+            Ok(Some(blobs)) => {
+                let repo_path = repo_path.clone();
+                let (count, bytes) = crate::locks::spawn_blocking(move || count_missing_blobs(&repo_path, &blobs))
+                    .await
+                    .map_err(|e| format!("预估同步数据量异常终止: {}", e))?
+                    .map_err(|e| e.to_string())?;
+                estimate.download_objects = count;
+                estimate.download_bytes = bytes;
+                estimate.download_known = true;
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("estimate_sync_size: 无法通过 GitHub API 估算下载量: {}", e),
+        }
+    }
+    Ok(estimate)
+}
+
+/// 查询当前版本支持的同步传输方式（HTTPS / SSH）
+/// 
+/// 前端调用: `invoke('sync_capabilities')`
+#[tauri::command]
+pub fn sync_capabilities() -> SyncCapabilities {
+    crate::git::sync_capabilities()
+}
+
 /// 从远程仓库获取更新（fetch）
 /// 
-/// 前端调用: `invoke('fetch_from_remote', { path: '...', remoteName: 'origin', patToken: '...' })`
+/// 传入 depth 时执行浅获取（例如手机端首次同步传 1），之后可调用 unshallow_repository 补全历史
+/// 
+/// 前端调用: `invoke('fetch_from_remote', { path: '...', remoteName: 'origin', patToken: '...', depth: 1 })`
 #[tauri::command]
-pub fn fetch_from_remote(path: String, remote_name: String, pat_token: Option<String>) -> Result<(), String> {
-    crate::git::fetch_from_remote(
+pub fn fetch_from_remote(
+    path: String,
+    remote_name: String,
+    pat_token: Option<String>,
+    depth: Option<u32>,
+) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
+    crate::git::fetch_from_remote_with_depth(
         PathBuf::from(path).as_path(),
         &remote_name,
         pat_token.as_deref(),
+        depth,
     )
     .map_err(|e| e.to_string())
 }
 
-/// 推送本地提交到远程仓库（push）
+/// 补全浅获取省略的历史
 /// 
-/// 前端调用: `invoke('push_to_remote', { path: '...', remoteName: 'origin', branchName: 'main', patToken: '...' })`
+/// 前端调用: `invoke('unshallow_repository', { path: '...', remoteName: 'origin', patToken: '...' })`
 #[tauri::command]
-pub fn push_to_remote(
+pub fn unshallow_repository(path: String, remote_name: String, pat_token: Option<String>) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
+    crate::git::unshallow_repository(
+        PathBuf::from(path).as_path(),
+        &remote_name,
+        pat_token.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 获取本地分支相对远程分支的领先/落后提交数
+/// 
+/// 传入 patToken 时会先 fetch 刷新远程引用；返回 `[ahead, behind]`
+/// 
+/// 前端调用: `invoke('get_ahead_behind_command', { path: '...', remoteName: 'origin', branchName: 'main', patToken: '...' })`
+#[tauri::command]
+pub fn get_ahead_behind_command(
     path: String,
     remote_name: String,
     branch_name: String,
     pat_token: Option<String>,
-) -> Result<(), String> {
-    crate::git::push_to_remote(
+) -> Result<(usize, usize), String> {
+    let _repo_lock = lock_repo_blocking();
+    get_ahead_behind(
         PathBuf::from(path).as_path(),
         &remote_name,
         &branch_name,
@@ -682,6 +1915,88 @@ pub fn push_to_remote(
     .map_err(|e| e.to_string())
 }
 
+/// 把尚未推送的本地提交合并为一个提交（不 fetch、不 push）
+/// 
+/// 前端调用: `invoke('squash_unpushed_commits_command', { path: '...', remoteName: 'origin', branchName: 'main', message: '...' })`
+#[tauri::command]
+pub fn squash_unpushed_commits_command(
+    path: String,
+    remote_name: String,
+    branch_name: String,
+    message: Option<String>,
+) -> Result<SquashResult, String> {
+    let _repo_lock = lock_repo_blocking();
+    squash_unpushed_commits(
+        PathBuf::from(path).as_path(),
+        &remote_name,
+        &branch_name,
+        message.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 列出本地分支中尚未推送到远程的文件（不会 fetch，使用上次抓取的远程引用）
+/// 
+/// 前端调用: `invoke('list_unsynced_changes_command', { path: '...', remoteName: 'origin', branchName: 'main' })`
+#[tauri::command]
+pub fn list_unsynced_changes_command(
+    path: String,
+    remote_name: String,
+    branch_name: String,
+) -> Result<Vec<String>, String> {
+    list_unsynced_changes(PathBuf::from(path).as_path(), &remote_name, &branch_name)
+        .map_err(|e| e.to_string())
+}
+
+/// 推送本地提交到远程仓库（push）
+/// 
+/// `verify` 为 true 时推送后再列出远程引用，确认远程分支已指向本地最新提交
+/// 
+/// 前端调用: `invoke('push_to_remote', { path: '...', remoteName: 'origin', branchName: 'main', patToken: '...', verify: true })`
+#[tauri::command]
+pub fn push_to_remote(
+    path: String,
+    remote_name: String,
+    branch_name: String,
+    pat_token: Option<String>,
+    verify: Option<bool>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
+    let repo_path = PathBuf::from(path);
+    crate::git::push_to_remote(&repo_path, &remote_name, &branch_name, pat_token.as_deref())
+        .map_err(|e| e.to_string())?;
+    if verify.unwrap_or(false) {
+        verify_remote_branch(&repo_path, &remote_name, &branch_name, pat_token.as_deref())
+            .map_err(|e| e.to_string())?;
+    }
+    events::emit_push_done(&app, &remote_name, &branch_name);
+    Ok(())
+}
+
+/// 压缩历史或从历史中删除文件后，强制推送覆盖远程分支
+/// 
+/// 远程分支已被其他设备更新（不在改写前记录的位置）时拒绝覆盖。成功后才能再次同步。
+/// 
+/// 前端调用: `invoke('force_push_with_lease', { path: '...', remoteName: 'origin', branchName: 'main', patToken: '...' })`
+#[tauri::command]
+pub fn force_push_with_lease(
+    path: String,
+    remote_name: String,
+    branch_name: String,
+    pat_token: Option<String>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
+    let repo_path = PathBuf::from(path);
+    crate::git::force_push_with_lease(&repo_path, &remote_name, &branch_name, pat_token.as_deref())
+        .map_err(|e| e.to_string())?;
+    verify_remote_branch(&repo_path, &remote_name, &branch_name, pat_token.as_deref())
+        .map_err(|e| e.to_string())?;
+    events::emit_push_done(&app, &remote_name, &branch_name);
+    Ok(())
+}
+
 /// 同步远程仓库（fetch + rebase/push）
 /// 
 /// 前端调用: `invoke('sync_with_remote', { path: '...', remoteName: 'origin', branchName: 'main', patToken: '...' })`
@@ -691,20 +2006,47 @@ pub fn sync_with_remote(
     remote_name: String,
     branch_name: String,
     pat_token: Option<String>,
+    app: AppHandle,
 ) -> Result<SyncResult, String> {
-    eprintln!("[sync_with_remote] 开始同步: path={}, remote={}, branch={}", path, remote_name, branch_name);
-    crate::git::sync_with_remote(
+    let _repo_lock = lock_repo_blocking();
+    tracing::info!("[sync_with_remote] 开始同步: path={}, remote={}, branch={}", path, remote_name, branch_name);
+    events::emit_sync_started(&app, &remote_name, &branch_name);
+    let result = crate::git::sync_with_remote(
         PathBuf::from(path).as_path(),
         &remote_name,
         &branch_name,
         pat_token.as_deref(),
-    )
-    .map_err(|e| {
-        eprintln!("[sync_with_remote] 同步失败: {}", e);
+    );
+    events::emit_sync_finished(&app, &result);
+    result.map_err(|e| {
+        tracing::warn!("[sync_with_remote] 同步失败: {}", e);
         e.to_string()
     })
 }
 
+/// 从远程拉取（fetch + fast-forward/rebase），不推送本地提交
+/// 
+/// 前端调用: `invoke('pull_command', { path: '...', remoteName: 'origin', branchName: 'main', patToken: '...' })`
+#[tauri::command]
+pub fn pull_command(
+    path: String,
+    remote_name: String,
+    branch_name: String,
+    pat_token: Option<String>,
+    app: AppHandle,
+) -> Result<SyncResult, String> {
+    let _repo_lock = lock_repo_blocking();
+    events::emit_sync_started(&app, &remote_name, &branch_name);
+    let result = pull(
+        PathBuf::from(path).as_path(),
+        &remote_name,
+        &branch_name,
+        pat_token.as_deref(),
+    );
+    events::emit_sync_finished(&app, &result);
+    result.map_err(|e| e.to_string())
+}
+
 /// 启动同步（fetch + fast-forward/rebase），如遇冲突返回结构化冲突信息
 /// 
 /// 前端调用: `invoke('begin_sync', { path: '...', remoteName: 'origin', branchName: 'main', patToken: '...' })`
@@ -714,14 +2056,18 @@ pub fn begin_sync(
     remote_name: String,
     branch_name: String,
     pat_token: Option<String>,
+    app: AppHandle,
 ) -> Result<SyncResult, String> {
-    crate::git::sync_with_remote(
+    let _repo_lock = lock_repo_blocking();
+    events::emit_sync_started(&app, &remote_name, &branch_name);
+    let result = crate::git::sync_with_remote(
         PathBuf::from(path).as_path(),
         &remote_name,
         &branch_name,
         pat_token.as_deref(),
-    )
-    .map_err(|e| e.to_string())
+    );
+    events::emit_sync_finished(&app, &result);
+    result.map_err(|e| e.to_string())
 }
 
 /// 继续同步（继续进行中的 rebase）
@@ -729,6 +2075,7 @@ pub fn begin_sync(
 /// 前端调用: `invoke('continue_sync', { path: '...', branchName: 'main' })`
 #[tauri::command]
 pub fn continue_sync_command(path: String, branch_name: String) -> Result<SyncResult, String> {
+    let _repo_lock = lock_repo_blocking();
     continue_sync(PathBuf::from(path).as_path(), &branch_name).map_err(|e| e.to_string())
 }
 
@@ -737,15 +2084,57 @@ pub fn continue_sync_command(path: String, branch_name: String) -> Result<SyncRe
 /// 前端调用: `invoke('abort_sync', { path: '...' })`
 #[tauri::command]
 pub fn abort_sync_command(path: String) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
     abort_sync(PathBuf::from(path).as_path()).map_err(|e| e.to_string())
 }
 
 /// 解决冲突（写入工作区 + stage），随后应调用 `continue_sync`
 ///
+/// 选择 `Merge` 时按共同祖先三方合并，返回仍带冲突标记、需要用户编辑的文件
+///
 /// 前端调用: `invoke('resolve_conflict', { path: '...', items: [{ path: 'a.md', choice: 'CopyBoth' }] })`
 #[tauri::command]
-pub fn resolve_conflict_command(path: String, items: Vec<ConflictResolutionItem>) -> Result<(), String> {
-    resolve_conflict(PathBuf::from(path).as_path(), items).map_err(|e| e.to_string())
+pub async fn resolve_conflict_command(
+    path: String,
+    items: Vec<ConflictResolutionItem>,
+    app: AppHandle,
+) -> Result<Vec<String>, String> {
+    // 只有三方合并需要解密笔记；只选择保留一方时不读取密钥
+    let needs_key = items.iter().any(|item| item.choice == ConflictResolutionChoice::Merge);
+    let (master_key, compress) = if needs_key {
+        let master_key = get_or_create_master_key(&app)
+            .await
+            .map_err(|e| format!("无法获取主加密密钥: {}", e))?;
+        let compress = read_workspace_config_or_default(&app, "resolve_conflict_command")
+            .await
+            .compress_notes;
+        (Some(master_key), compress)
+    } else {
+        (None, false)
+    };
+
+    let path = PathBuf::from(path);
+    run_git_blocking(move || resolve_conflict(&path, items, master_key.as_deref(), compress)).await
+}
+
+/// 搁置未提交的 .enc 更改（同步前调用）
+/// 
+/// 前端调用: `invoke('shelve_changes_command', { path: '...' })`
+#[tauri::command]
+pub fn shelve_changes_command(path: String) -> Result<Option<String>, String> {
+    let _repo_lock = lock_repo_blocking();
+    shelve_changes(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 恢复搁置的更改（同步后调用）
+/// 
+/// 前端调用: `invoke('unshelve_changes_command', { path: '...' })`
+#[tauri::command]
+pub fn unshelve_changes_command(path: String) -> Result<UnshelveResult, String> {
+    let _repo_lock = lock_repo_blocking();
+    unshelve_changes(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
 }
 
 /// 获取当前分支名
@@ -757,11 +2146,137 @@ pub fn get_current_branch_command(path: String) -> Result<String, String> {
         .map_err(|e| e.to_string())
 }
 
+/// 重命名本地分支（例如给自动生成的冲突分支起一个好记的名字）
+/// 
+/// 前端调用: `invoke('rename_branch_command', { path: '...', oldName: '...', newName: '...' })`
+#[tauri::command]
+pub fn rename_branch_command(path: String, old_name: String, new_name: String) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
+    rename_branch(PathBuf::from(path).as_path(), &old_name, &new_name)
+        .map_err(|e| e.to_string())
+}
+
+/// 列出未处理的冲突分支（`conflict_*`）及其领先提交数和修改的文件
+/// 
+/// 前端调用: `invoke('list_conflicts_command', { path: '...' })`
+#[tauri::command]
+pub fn list_conflicts_command(path: String) -> Result<Vec<ConflictBranchInfo>, String> {
+    list_conflicts(PathBuf::from(path).as_path())
+        .map_err(|e| e.to_string())
+}
+
+/// 将源分支合并到目标分支（例如把审阅过的冲突分支合并回 main）
+/// 
+/// 双方都修改过的笔记会解密后按明文三方合并，无法自动合并的部分写入冲突标记
+/// 
+/// 前端调用: `invoke('merge_branch_command', { path: '...', sourceBranch: 'conflict_...', intoBranch: 'main' })`
+#[tauri::command]
+pub async fn merge_branch_command(
+    path: String,
+    source_branch: String,
+    into_branch: String,
+    app: AppHandle,
+) -> Result<MergeResult, String> {
+    let master_key = get_or_create_master_key(&app)
+        .await
+        .map_err(|e| format!("无法获取主加密密钥: {}", e))?;
+    let compress = read_workspace_config_or_default(&app, "merge_branch_command")
+        .await
+        .compress_notes;
+
+    run_git_blocking(move || {
+        merge_branch(
+            PathBuf::from(path).as_path(),
+            &source_branch,
+            &into_branch,
+            &master_key,
+            compress,
+        )
+    })
+    .await
+}
+
+/// 将另一个分支干净地合并进来并创建合并提交（保留两条历史；有冲突时报错）
+/// 
+/// 前端调用: `invoke('create_merge_commit_command', { path: '...', branch: 'main', otherBranch: '...', message: '...' })`
+#[tauri::command]
+pub async fn create_merge_commit_command(
+    path: String,
+    branch: String,
+    other_branch: String,
+    message: Option<String>,
+) -> Result<String, String> {
+    let message = message.unwrap_or_else(|| format!("Merge branch '{}' into {}", other_branch, branch));
+    run_git_blocking(move || {
+        create_merge_commit(PathBuf::from(path).as_path(), &branch, &other_branch, &message)
+    })
+    .await
+}
+
+/// 处理冲突分支：合并到主分支（`merge`）或丢弃（`discard`），完成后删除该分支
+/// 
+/// 前端调用: `invoke('resolve_conflict_branch_command', { path: '...', branch: 'conflict_...', action: 'merge' })`
+#[tauri::command]
+pub async fn resolve_conflict_branch_command(
+    path: String,
+    branch: String,
+    action: ConflictBranchAction,
+    app: AppHandle,
+) -> Result<Option<MergeResult>, String> {
+    let master_key = get_or_create_master_key(&app)
+        .await
+        .map_err(|e| format!("无法获取主加密密钥: {}", e))?;
+    let compress = read_workspace_config_or_default(&app, "resolve_conflict_branch_command")
+        .await
+        .compress_notes;
+
+    run_git_blocking(move || {
+        resolve_conflict_branch(
+            PathBuf::from(path).as_path(),
+            &branch,
+            action,
+            &master_key,
+            compress,
+        )
+    })
+    .await
+}
+
+/// 将单个提交应用到目标分支（cherry-pick）
+/// 
+/// 前端调用: `invoke('cherry_pick_command', { path: '...', commitSha: '...', ontoBranch: 'main' })`
+#[tauri::command]
+pub async fn cherry_pick_command(
+    path: String,
+    commit_sha: String,
+    onto_branch: String,
+    app: AppHandle,
+) -> Result<MergeResult, String> {
+    let master_key = get_or_create_master_key(&app)
+        .await
+        .map_err(|e| format!("无法获取主加密密钥: {}", e))?;
+    let compress = read_workspace_config_or_default(&app, "cherry_pick_command")
+        .await
+        .compress_notes;
+
+    run_git_blocking(move || {
+        cherry_pick(
+            PathBuf::from(path).as_path(),
+            &commit_sha,
+            &onto_branch,
+            &master_key,
+            compress,
+        )
+    })
+    .await
+}
+
 /// 切换到指定分支
 /// 
 /// 前端调用: `invoke('switch_to_branch', { path: '...', branch: 'main' })`
 #[tauri::command]
 pub fn switch_to_branch_command(path: String, branch: String) -> Result<(), String> {
+    let _repo_lock = lock_repo_blocking();
     switch_to_branch(PathBuf::from(path).as_path(), &branch)
         .map_err(|e| e.to_string())
 }
@@ -780,6 +2295,35 @@ pub async fn search_files_command(
         .map_err(|e| e.to_string())
 }
 
+/// 用持久化索引搜索文档内容（分页；首次调用时建立索引）
+///
+/// 前端调用: `invoke('search_indexed_command', { workspacePath: '...', query: '...', offset: 0, limit: 50 })`
+#[tauri::command]
+pub async fn search_indexed_command(
+    workspace_path: String,
+    query: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    app: AppHandle,
+) -> Result<SearchPage, String> {
+    search_indexed(&workspace_path, &query, offset.unwrap_or(0), limit.unwrap_or(50), &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 丢弃并重建搜索索引
+///
+/// 前端调用: `invoke('rebuild_search_index_command', { workspacePath: '...' })`
+#[tauri::command]
+pub async fn rebuild_search_index_command(
+    workspace_path: String,
+    app: AppHandle,
+) -> Result<SearchIndexStats, String> {
+    rebuild_search_index(&workspace_path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 /// 保存导出文件到 Documents/vana 目录
 ///
 /// 前端调用: `invoke('save_export_file', { filename: '...', content: [...], fileType: 'pdf' | 'docx' })`
@@ -816,3 +2360,197 @@ pub async fn save_export_file(
     Ok(final_path.to_string_lossy().to_string())
 }
 
+/// 导出整库加密备份包
+///
+/// 前端调用: `invoke('export_vault_archive_command', { workspacePath: '...', outPath: '...', passphrase: '...' })`
+#[tauri::command]
+pub async fn export_vault_archive_command(
+    workspace_path: String,
+    out_path: String,
+    passphrase: String,
+    app: AppHandle,
+) -> Result<ArchiveSummary, String> {
+    export_vault_archive(&workspace_path, &out_path, &passphrase, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 从加密备份包恢复笔记
+///
+/// 前端调用: `invoke('import_vault_archive_command', { workspacePath: '...', archivePath: '...', passphrase: '...' })`
+#[tauri::command]
+pub async fn import_vault_archive_command(
+    workspace_path: String,
+    archive_path: String,
+    passphrase: String,
+    app: AppHandle,
+) -> Result<ArchiveSummary, String> {
+    import_vault_archive(&workspace_path, &archive_path, &passphrase, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 导入外部明文目录到加密工作区
+///
+/// 前端调用: `invoke('import_plaintext_tree_command', { sourceDir: '...', destRelativePath: 'imported' })`
+#[tauri::command]
+pub async fn import_plaintext_tree_command(
+    source_dir: String,
+    dest_relative_path: String,
+    app: AppHandle,
+) -> Result<ArchiveSummary, String> {
+    import_plaintext_tree(&source_dir, &dest_relative_path, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 以指定编码导入外部文本文件（GBK、Shift-JIS 等旧编码会转码为 UTF-8 后加密保存）
+///
+/// 前端调用: `invoke('import_file_with_encoding_command', { sourcePath: '...', dest: 'imported/note.md', encoding: 'gbk' })`
+#[tauri::command]
+pub async fn import_file_with_encoding_command(
+    source_path: String,
+    dest: String,
+    encoding: String,
+    app: AppHandle,
+) -> Result<(), String> {
+    import_file_with_encoding(&source_path, &dest, &encoding, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 解密导出整个工作区为明文目录
+///
+/// 前端调用: `invoke('export_plaintext_tree_command', { workspacePath: '...', destDir: '...' })`
+#[tauri::command]
+pub async fn export_plaintext_tree_command(
+    workspace_path: String,
+    dest_dir: String,
+    app: AppHandle,
+) -> Result<ArchiveSummary, String> {
+    export_plaintext_tree(&workspace_path, &dest_dir, &app)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{commit_changes, commit_changes_on_branch, init_repository, switch_to_branch};
+
+    fn temp_repo(name: &str) -> PathBuf {
+        let dir = crate::test_util::temp_workspace(&format!("commands_{}", name)).into_path();
+        init_repository(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_config_file_plaintext_detection_and_migration() {
+        let key = [4u8; 32];
+        // 开启加密之前写入的明文 .vnode.json
+        let plaintext = serde_json::to_string_pretty(&AtmosphereConfig { theme: "rusty".to_string() }).unwrap();
+        let legacy = encode_config(&plaintext, None).unwrap();
+        assert!(is_plaintext_config(&legacy));
+
+        // 开启加密后下一次写入迁移为密文，密文不会被误认为明文
+        let encrypted = encode_config(&plaintext, Some(&key)).unwrap();
+        assert!(!is_plaintext_config(&encrypted));
+        let decrypted = decrypt_config_bytes(&encrypted, &key).unwrap();
+        let config: AtmosphereConfig = serde_json::from_str(&decrypted).unwrap();
+        assert_eq!(config.theme, "rusty");
+
+        // 密钥错误（例如本机生成了另一把密钥）时报错，不能当作空配置
+        let err = decrypt_config_bytes(&encrypted, &[5u8; 32]).unwrap_err();
+        assert!(err.contains("无法解密配置文件"));
+        assert!(!is_plaintext_config(b"{\"theme\": "));
+    }
+
+    /// main 上有一个提交，`branch` 在其基础上多一个提交，当前检出 main
+    fn repo_with_branch(name: &str, branch: &str) -> PathBuf {
+        let repo_path = temp_repo(name);
+        std::fs::write(repo_path.join("a.md.enc"), "a").unwrap();
+        commit_changes(&repo_path, "base").unwrap();
+        let repo = git2::Repository::open(&repo_path).unwrap();
+        let base = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch(branch, &base, false).unwrap();
+        switch_to_branch(&repo_path, branch).unwrap();
+        std::fs::write(repo_path.join("b.md.enc"), "b").unwrap();
+        commit_changes_on_branch(&repo_path, "branch edit", branch).unwrap();
+        switch_to_branch(&repo_path, "main").unwrap();
+        assert!(!repo_path.join("b.md.enc").exists());
+        repo_path
+    }
+
+    #[tokio::test]
+    async fn test_merge_into_checked_out_branch_from_async_command() {
+        // 准备仓库时的检出同样不能在 tokio 工作线程上执行
+        let repo_path = crate::locks::spawn_blocking(|| repo_with_branch("merge_async", "feature")).await.unwrap();
+        let path = repo_path.clone();
+        let result = run_git_blocking(move || merge_branch(&path, "feature", "main", &[0u8; 32], false))
+            .await
+            .unwrap();
+        assert!(result.conflicted_paths.is_empty());
+        assert!(repo_path.join("b.md.enc").exists());
+
+        let _ = std::fs::remove_dir_all(&repo_path);
+    }
+
+    #[tokio::test]
+    async fn test_cherry_pick_onto_checked_out_branch_from_async_command() {
+        let repo_path = crate::locks::spawn_blocking(|| repo_with_branch("cherry_pick_async", "feature")).await.unwrap();
+        let feature_tip = git2::Repository::open(&repo_path).unwrap()
+            .revparse_single("feature").unwrap().id().to_string();
+        let path = repo_path.clone();
+        let result = run_git_blocking(move || cherry_pick(&path, &feature_tip, "main", &[0u8; 32], false))
+            .await
+            .unwrap();
+        assert!(result.conflicted_paths.is_empty());
+        assert!(repo_path.join("b.md.enc").exists());
+
+        let _ = std::fs::remove_dir_all(&repo_path);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_checked_out_conflict_branch_from_async_command() {
+        let repo_path = crate::locks::spawn_blocking(|| {
+            let repo_path = repo_with_branch("resolve_branch_async", "conflict_1700000000");
+            // 冲突分支处于检出状态：需要先切回 main，再合并
+            switch_to_branch(&repo_path, "conflict_1700000000").unwrap();
+            repo_path
+        })
+        .await
+        .unwrap();
+        let path = repo_path.clone();
+        let merged = run_git_blocking(move || {
+            resolve_conflict_branch(&path, "conflict_1700000000", ConflictBranchAction::Merge, &[0u8; 32], false)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(merged.conflicted_paths.is_empty());
+        assert_eq!(crate::git::get_current_branch(&repo_path).unwrap(), "main");
+        assert!(repo_path.join("b.md.enc").exists());
+
+        let _ = std::fs::remove_dir_all(&repo_path);
+    }
+
+    #[tokio::test]
+    async fn test_create_merge_commit_on_checked_out_branch_from_async_command() {
+        let repo_path = crate::locks::spawn_blocking(|| repo_with_branch("merge_commit_async", "feature")).await.unwrap();
+        let sha = create_merge_commit_command(
+            repo_path.to_string_lossy().to_string(),
+            "main".to_string(),
+            "feature".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+        let repo = git2::Repository::open(&repo_path).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.id().to_string(), sha);
+        assert_eq!(head.parent_count(), 2);
+        assert!(repo_path.join("b.md.enc").exists());
+
+        let _ = std::fs::remove_dir_all(&repo_path);
+    }
+}
@@ -3,10 +3,33 @@
 // 实现零知识加密，确保即使文件泄露也无法解密内容
 
 use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Key, Nonce,
 };
 use anyhow::{Context, Result};
+use argon2::Argon2;
+
+/// 口令派生密钥时使用的盐长度（字节）
+pub const PASSPHRASE_SALT_LEN: usize = 16;
+
+// 笔记明文封装格式（位于密文内部，受 GCM 认证标签保护）：
+// v1: NOTE_MAGIC (5) + VERSION (1) + 元数据长度 u32 LE (4) + 元数据 JSON + 正文
+// v2: NOTE_MAGIC (5) + VERSION (1) + FLAGS (1) + 元数据长度 u32 LE (4) + 元数据 JSON + 正文
+// 旧版本写入的文件没有封装头，解密后整体视为正文
+const NOTE_MAGIC: &[u8; 5] = b"\0VNM\0";
+const NOTE_VERSION: u8 = 2;
+// 正文使用 zstd 压缩
+const NOTE_FLAG_ZSTD: u8 = 0x01;
+const NOTE_ZSTD_LEVEL: i32 = 3;
+
+/// 笔记的逻辑时间戳（RFC 3339）
+/// 
+/// 文件系统 mtime 会被 git checkout 重置，这里记录真实的创建/最后编辑时间
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct NoteMeta {
+    pub created: String,
+    pub modified: String,
+}
 
 /// 加密明文内容
 /// 
@@ -16,7 +39,39 @@ use anyhow::{Context, Result};
 /// 
 /// # 返回
 /// 返回加密后的密文（包含认证标签）
+/// 
+/// 注意：不写入笔记元数据封装头，笔记文件请使用 `encrypt_note`（本函数用于配置文件等非笔记内容）
 pub fn encrypt_content(plaintext: &str, key: &[u8]) -> Result<Vec<u8>> {
+    encrypt_bytes(plaintext.as_bytes(), key)
+}
+
+/// 解密密文内容
+/// 
+/// # 参数
+/// - `ciphertext`: 加密后的内容（包含 nonce 和密文）
+/// - `key`: 32 字节的加密密钥
+/// 
+/// # 返回
+/// 返回解密后的明文内容
+/// 
+/// 注意：不解析笔记元数据封装头，笔记文件请使用 `decrypt_note`（本函数用于配置文件等非笔记内容）
+pub fn decrypt_content(ciphertext: &[u8], key: &[u8]) -> Result<String> {
+    let plaintext_bytes = decrypt_bytes(ciphertext, key)?;
+
+    // 转换为字符串
+    String::from_utf8(plaintext_bytes)
+        .context("解密后的内容不是有效的 UTF-8 字符串")
+}
+
+/// 加密任意字节内容
+/// 
+/// # 参数
+/// - `plaintext`: 要加密的字节
+/// - `key`: 32 字节的加密密钥
+/// 
+/// # 返回
+/// 返回 nonce (12 bytes) + 密文（包含认证标签）
+pub fn encrypt_bytes(plaintext: &[u8], key: &[u8]) -> Result<Vec<u8>> {
     // 验证密钥长度（AES-256 需要 32 字节）
     if key.len() != 32 {
         anyhow::bail!("密钥长度必须为 32 字节（AES-256）");
@@ -31,7 +86,7 @@ pub fn encrypt_content(plaintext: &str, key: &[u8]) -> Result<Vec<u8>> {
 
     // 加密内容
     let ciphertext = cipher
-        .encrypt(&nonce, plaintext.as_bytes())
+        .encrypt(&nonce, plaintext)
         .map_err(|e| anyhow::anyhow!("加密失败: {:?}", e))?;
 
     // 将 nonce 和密文组合：nonce (12 bytes) + ciphertext
@@ -42,15 +97,15 @@ pub fn encrypt_content(plaintext: &str, key: &[u8]) -> Result<Vec<u8>> {
     Ok(result)
 }
 
-/// 解密密文内容
+/// 解密任意字节内容
 /// 
 /// # 参数
 /// - `ciphertext`: 加密后的内容（包含 nonce 和密文）
 /// - `key`: 32 字节的加密密钥
 /// 
 /// # 返回
-/// 返回解密后的明文内容
-pub fn decrypt_content(ciphertext: &[u8], key: &[u8]) -> Result<String> {
+/// 返回解密后的原始字节
+pub fn decrypt_bytes(ciphertext: &[u8], key: &[u8]) -> Result<Vec<u8>> {
     // 验证密钥长度
     if key.len() != 32 {
         anyhow::bail!("密钥长度必须为 32 字节（AES-256）");
@@ -70,13 +125,130 @@ pub fn decrypt_content(ciphertext: &[u8], key: &[u8]) -> Result<String> {
     let nonce = Nonce::from_slice(nonce_bytes);
 
     // 解密内容
-    let plaintext_bytes = cipher
+    cipher
         .decrypt(nonce, encrypted_data)
-        .map_err(|e| anyhow::anyhow!("解密失败：可能是密钥错误或数据损坏: {:?}", e))?;
+        .map_err(|e| anyhow::anyhow!("解密失败：可能是密钥错误或数据损坏: {:?}", e))
+}
 
-    // 转换为字符串
-    String::from_utf8(plaintext_bytes)
-        .context("解密后的内容不是有效的 UTF-8 字符串")
+/// 加密笔记正文及其元数据
+/// 
+/// # 参数
+/// - `content`: 笔记正文
+/// - `meta`: 创建/修改时间
+/// - `compress`: 是否在加密前用 zstd 压缩正文（压缩后不更小时仍保存原文）
+/// - `key`: 32 字节的加密密钥
+/// 
+/// # 返回
+/// 返回 nonce + 密文，元数据与正文一起受认证标签保护
+pub fn encrypt_note(content: &str, meta: &NoteMeta, compress: bool, key: &[u8]) -> Result<Vec<u8>> {
+    encrypt_note_bytes(content.as_bytes(), meta, compress, key)
+}
+
+/// 加密任意字节正文（例如导入的图片等非文本文件）及其元数据，格式与 `encrypt_note` 相同
+pub fn encrypt_note_bytes(content: &[u8], meta: &NoteMeta, compress: bool, key: &[u8]) -> Result<Vec<u8>> {
+    let meta_json = serde_json::to_vec(meta).context("序列化笔记元数据失败")?;
+
+    let mut flags = 0u8;
+    let mut body = std::borrow::Cow::Borrowed(content);
+    if compress {
+        let compressed = zstd::bulk::compress(content, NOTE_ZSTD_LEVEL)
+            .context("压缩笔记内容失败")?;
+        if compressed.len() < content.len() {
+            flags |= NOTE_FLAG_ZSTD;
+            body = std::borrow::Cow::Owned(compressed);
+        }
+    }
+
+    let mut plaintext = Vec::with_capacity(NOTE_MAGIC.len() + 6 + meta_json.len() + body.len());
+    plaintext.extend_from_slice(NOTE_MAGIC);
+    plaintext.push(NOTE_VERSION);
+    plaintext.push(flags);
+    plaintext.extend_from_slice(&(meta_json.len() as u32).to_le_bytes());
+    plaintext.extend_from_slice(&meta_json);
+    plaintext.extend_from_slice(&body);
+
+    encrypt_bytes(&plaintext, key)
+}
+
+/// 解密笔记，返回正文字节和元数据
+/// 
+/// 兼容没有封装头的旧文件：此时元数据为 None，解密结果整体作为正文。
+pub fn decrypt_note_bytes(ciphertext: &[u8], key: &[u8]) -> Result<(Vec<u8>, Option<NoteMeta>)> {
+    let plaintext = decrypt_bytes(ciphertext, key)?;
+
+    if !plaintext.starts_with(NOTE_MAGIC) {
+        return Ok((plaintext, None));
+    }
+
+    let header = &plaintext[NOTE_MAGIC.len()..];
+    let (flags, rest) = match header.first() {
+        Some(1) => (0u8, &header[1..]),
+        Some(2) if header.len() >= 2 => (header[1], &header[2..]),
+        Some(version) => anyhow::bail!("不支持的笔记格式版本: {}", version),
+        None => anyhow::bail!("笔记封装头不完整"),
+    };
+    if flags & !NOTE_FLAG_ZSTD != 0 {
+        anyhow::bail!("不支持的笔记格式标志: {:#04x}", flags);
+    }
+    if rest.len() < 4 {
+        anyhow::bail!("笔记封装头不完整");
+    }
+
+    let meta_len = u32::from_le_bytes(rest[..4].try_into().expect("长度字段固定为 4 字节")) as usize;
+    let rest = &rest[4..];
+    if meta_len > rest.len() {
+        anyhow::bail!("笔记元数据长度无效");
+    }
+
+    let meta: NoteMeta = serde_json::from_slice(&rest[..meta_len])
+        .context("解析笔记元数据失败")?;
+    let body = &rest[meta_len..];
+
+    let body = if flags & NOTE_FLAG_ZSTD != 0 {
+        zstd::stream::decode_all(body).context("解压笔记内容失败")?
+    } else {
+        body.to_vec()
+    };
+
+    Ok((body, Some(meta)))
+}
+
+/// 解密笔记，返回正文字符串和元数据
+pub fn decrypt_note(ciphertext: &[u8], key: &[u8]) -> Result<(String, Option<NoteMeta>)> {
+    let (body, meta) = decrypt_note_bytes(ciphertext, key)?;
+    let content = String::from_utf8(body)
+        .context("解密后的内容不是有效的 UTF-8 字符串")?;
+    Ok((content, meta))
+}
+
+/// 生成口令派生密钥所需的随机盐
+pub fn generate_salt() -> [u8; PASSPHRASE_SALT_LEN] {
+    let mut salt = [0u8; PASSPHRASE_SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// 从用户口令派生 32 字节密钥（Argon2id）
+/// 
+/// 用于备份包等需要脱离主密钥、仅凭口令即可恢复的场景。
+/// 
+/// # 参数
+/// - `passphrase`: 用户口令（不能为空）
+/// - `salt`: 随机盐（与密文一同保存）
+/// 
+/// # 返回
+/// 返回 32 字节的派生密钥
+pub fn derive_key_from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Vec<u8>> {
+    if passphrase.is_empty() {
+        anyhow::bail!("口令不能为空");
+    }
+
+    let mut key = vec![0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("口令派生密钥失败: {}", e))?;
+
+    Ok(key)
 }
 
 #[cfg(test)]
@@ -114,5 +286,53 @@ mod tests {
             decrypt_content(&ciphertext2, &key).unwrap()
         );
     }
-}
 
+    #[test]
+    fn test_encrypt_decrypt_bytes() {
+        let key = [7u8; 32];
+        let data: Vec<u8> = (0..=255u8).collect();
+
+        let ciphertext = encrypt_bytes(&data, &key).unwrap();
+        assert_eq!(decrypt_bytes(&ciphertext, &key).unwrap(), data);
+    }
+
+    #[test]
+    fn test_note_meta_round_trip_and_legacy_files() {
+        let key = [3u8; 32];
+        let meta = NoteMeta {
+            created: "2024-01-01T00:00:00+00:00".to_string(),
+            modified: "2024-02-01T12:30:00+00:00".to_string(),
+        };
+
+        let ciphertext = encrypt_note("# 标题\n正文", &meta, false, &key).unwrap();
+        let (content, decoded) = decrypt_note(&ciphertext, &key).unwrap();
+        assert_eq!(content, "# 标题\n正文");
+        assert_eq!(decoded.as_ref(), Some(&meta));
+
+        // 压缩后的笔记可以还原，且明显小于未压缩版本
+        let markdown = "## 今日记录\n- 一条重复的列表项，用来模拟真实的笔记内容\n".repeat(2000);
+        let plain = encrypt_note(&markdown, &meta, false, &key).unwrap();
+        let compressed = encrypt_note(&markdown, &meta, true, &key).unwrap();
+        assert!(compressed.len() * 4 < plain.len());
+        assert_eq!(decrypt_note(&compressed, &key).unwrap().0, markdown);
+
+        // 旧格式文件没有封装头
+        let legacy = encrypt_content("旧笔记", &key).unwrap();
+        assert_eq!(decrypt_note(&legacy, &key).unwrap(), ("旧笔记".to_string(), None));
+    }
+
+    #[test]
+    fn test_derive_key_from_passphrase() {
+        let salt = generate_salt();
+
+        // 相同口令 + 相同盐 => 相同密钥；不同口令 => 不同密钥
+        let key1 = derive_key_from_passphrase("correct horse", &salt).unwrap();
+        let key2 = derive_key_from_passphrase("correct horse", &salt).unwrap();
+        let key3 = derive_key_from_passphrase("battery staple", &salt).unwrap();
+        assert_eq!(key1.len(), 32);
+        assert_eq!(key1, key2);
+        assert_ne!(key1, key3);
+
+        assert!(derive_key_from_passphrase("", &salt).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+// No Visitors - 事件模块
+// 将提交、同步、推送的生命周期以 Tauri 事件通知前端，
+// 前端据此驱动实时状态指示，而不必轮询仓库状态。
+
+use crate::git::SyncResult;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+/// 提交完成
+pub const COMMIT_DONE: &str = "commit:done";
+/// 同步开始
+pub const SYNC_STARTED: &str = "sync:started";
+/// 同步遇到冲突
+pub const SYNC_CONFLICT: &str = "sync:conflict";
+/// 同步结束（无论成功与否）
+pub const SYNC_DONE: &str = "sync:done";
+/// 推送完成
+pub const PUSH_DONE: &str = "push:done";
+/// 工作区文件在应用之外发生变化（同步、checkout、外部工具）
+pub const WORKSPACE_CHANGED: &str = "workspace:changed";
+
+/// `commit:done` 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitDonePayload {
+    pub sha: String,
+    pub message: String,
+}
+
+/// `sync:started` / `push:done` 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteTargetPayload {
+    pub remote_name: String,
+    pub branch_name: String,
+}
+
+/// `sync:conflict` 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncConflictPayload {
+    pub conflict_branch: Option<String>,
+    pub conflicted_files: usize,
+    pub conflicted_paths: Vec<String>,
+}
+
+/// `sync:done` 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncDonePayload {
+    pub success: bool,
+    pub has_conflict: bool,
+    pub error: Option<String>,
+}
+
+/// `workspace:changed` 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkspaceChangedPayload {
+    pub paths: Vec<String>,
+}
+
+/// 发送事件；发送失败只记录日志，不影响 Git 操作本身
+fn emit<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
+    if let Err(e) = app.emit(event, payload) {
+        tracing::warn!("[Events] 发送事件 {} 失败: {}", event, e);
+    }
+}
+
+/// 通知前端提交完成
+pub fn emit_commit_done(app: &AppHandle, sha: &str, message: &str) {
+    emit(app, COMMIT_DONE, CommitDonePayload {
+        sha: sha.to_string(),
+        message: message.to_string(),
+    });
+}
+
+/// 通知前端同步开始
+pub fn emit_sync_started(app: &AppHandle, remote_name: &str, branch_name: &str) {
+    emit(app, SYNC_STARTED, RemoteTargetPayload {
+        remote_name: remote_name.to_string(),
+        branch_name: branch_name.to_string(),
+    });
+}
+
+/// 根据同步结果通知前端（有冲突时先发送 `sync:conflict`，随后总是发送 `sync:done`）
+pub fn emit_sync_finished<E: std::fmt::Display>(app: &AppHandle, result: &Result<SyncResult, E>) {
+    match result {
+        Ok(sync) => {
+            if sync.has_conflict {
+                emit(app, SYNC_CONFLICT, SyncConflictPayload {
+                    conflict_branch: sync.conflict_branch.clone(),
+                    conflicted_files: sync.conflict.as_ref().map(|c| c.files.len()).unwrap_or(0),
+                    conflicted_paths: sync.conflicted_paths.clone(),
+                });
+            }
+            emit(app, SYNC_DONE, SyncDonePayload {
+                success: sync.success,
+                has_conflict: sync.has_conflict,
+                error: None,
+            });
+        }
+        Err(e) => {
+            emit(app, SYNC_DONE, SyncDonePayload {
+                success: false,
+                has_conflict: false,
+                error: Some(e.to_string()),
+            });
+        }
+    }
+}
+
+/// 通知前端推送完成
+pub fn emit_push_done(app: &AppHandle, remote_name: &str, branch_name: &str) {
+    emit(app, PUSH_DONE, RemoteTargetPayload {
+        remote_name: remote_name.to_string(),
+        branch_name: branch_name.to_string(),
+    });
+}
+
+/// 通知前端工作区文件发生变化（`paths` 为相对于工作区的路径）
+pub fn emit_workspace_changed(app: &AppHandle, paths: Vec<String>) {
+    emit(app, WORKSPACE_CHANGED, WorkspaceChangedPayload { paths });
+}
@@ -0,0 +1,207 @@
+// No Visitors - GitHub API 模块
+// 同步依赖 PAT 推送到远程，但只读 PAT 要到推送时才会失败。
+// 这里在保存 PAT 时通过 GitHub API 检查令牌是否有效、是否有推送权限。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const GITHUB_API_BASE: &str = "https://api.github.com";
+const GITHUB_API_TIMEOUT_SECS: u64 = 15;
+
+/// PAT 权限检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatScopeCheck {
+    pub valid: bool,               // 令牌是否被 GitHub 接受
+    pub can_push: Option<bool>,    // 对远程仓库是否有推送权限（无法判断时为 None）
+    pub scopes: Vec<String>,       // 经典 PAT 的 OAuth scopes（细粒度 PAT 没有 scopes，为空）
+    pub warning: Option<String>,   // 需要提示用户的问题
+}
+
+/// 从远程 URL 解析 GitHub 仓库的 owner/repo
+///
+/// 支持 `https://github.com/o/r(.git)`、带凭据的 `https://x@github.com/o/r` 和 `git@github.com:o/r.git`；
+/// 不是 github.com 的远程返回 None
+pub fn parse_github_repo(remote_url: &str) -> Option<(String, String)> {
+    let path = if let Some(rest) = remote_url
+        .strip_prefix("https://")
+        .or_else(|| remote_url.strip_prefix("http://"))
+    {
+        let host_and_path = rest.rsplit_once('@').map(|(_, after)| after).unwrap_or(rest);
+        host_and_path.strip_prefix("github.com/")?
+    } else {
+        remote_url.strip_prefix("git@github.com:")?
+    };
+
+    let mut parts = path.trim_end_matches('/').splitn(2, '/');
+    let owner = parts.next().filter(|s| !s.is_empty())?;
+    let repo = parts.next()?.trim_end_matches(".git");
+    if repo.is_empty() || repo.contains('/') {
+        return None;
+    }
+    Some((owner.to_string(), repo.to_string()))
+}
+
+#[derive(Deserialize)]
+struct RepoPermissions {
+    push: bool,
+}
+
+#[derive(Deserialize)]
+struct RepoResponse {
+    permissions: Option<RepoPermissions>,
+}
+
+/// 检查 PAT 是否有效、对远程仓库是否有推送权限
+///
+/// 只支持 github.com 远程；其他远程返回 `valid: true, can_push: None` 并附带说明。
+///
+/// # 参数
+/// - `pat`: 要检查的令牌
+/// - `remote_url`: 同步使用的远程 URL
+pub async fn check_pat_scopes(pat: &str, remote_url: &str) -> Result<PatScopeCheck> {
+    let Some((owner, repo)) = parse_github_repo(remote_url) else {
+        return Ok(PatScopeCheck {
+            valid: true,
+            can_push: None,
+            scopes: Vec::new(),
+            warning: Some("远程不是 GitHub 仓库，无法检查 PAT 权限".to_string()),
+        });
+    };
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(GITHUB_API_TIMEOUT_SECS))
+        .user_agent(concat!("vana/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("无法创建 HTTP 客户端")?;
+    let response = client
+        .get(format!("{}/repos/{}/{}", GITHUB_API_BASE, owner, repo))
+        .bearer_auth(pat)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .context("无法连接 GitHub API")?;
+
+    let scopes: Vec<String> = response
+        .headers()
+        .get("x-oauth-scopes")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let status = response.status();
+    let check = match status.as_u16() {
+        401 => PatScopeCheck {
+            valid: false,
+            can_push: Some(false),
+            scopes,
+            warning: Some("PAT 无效或已过期".to_string()),
+        },
+        // 私有仓库对无权限的令牌返回 404
+        403 | 404 => PatScopeCheck {
+            valid: true,
+            can_push: Some(false),
+            scopes,
+            warning: Some(format!("PAT 无法访问仓库 {}/{}，请检查令牌的仓库权限", owner, repo)),
+        },
+        _ if status.is_success() => {
+            let body: RepoResponse = response.json().await.context("无法解析 GitHub API 响应")?;
+            let can_push = body.permissions.map(|p| p.push);
+            let warning = match can_push {
+                Some(false) => Some("PAT 只有只读权限，同步时推送会失败（经典 PAT 需要 repo scope）".to_string()),
+                _ => None,
+            };
+            PatScopeCheck {
+                valid: true,
+                can_push,
+                scopes,
+                warning,
+            }
+        }
+        _ => anyhow::bail!("GitHub API 返回错误: {}", status),
+    };
+
+    if let Some(warning) = &check.warning {
+        tracing::warn!("[GitHub] {}/{}: {}", owner, repo, warning);
+    }
+    Ok(check)
+}
+
+#[derive(Deserialize)]
+struct TreeItem {
+    #[serde(rename = "type")]
+    kind: String,
+    sha: String,
+    size: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct TreeResponse {
+    tree: Vec<TreeItem>,
+    #[serde(default)]
+    truncated: bool,
+}
+
+/// 列出远程某个提交中的全部文件（blob SHA 与大小），用于不下载对象即可估算同步流量
+///
+/// 不是 github.com 的远程返回 None。加密笔记几乎无法压缩，文件大小即可近似为传输量。
+///
+/// # 参数
+/// - `pat`: 访问私有仓库所需的令牌（公开仓库可为 None）
+/// - `remote_url`: 同步使用的远程 URL
+/// - `commit_sha`: 远程分支的最新提交
+pub async fn list_tree_blobs(pat: Option<&str>, remote_url: &str, commit_sha: &str) -> Result<Option<Vec<(String, u64)>>> {
+    let Some((owner, repo)) = parse_github_repo(remote_url) else {
+        return Ok(None);
+    };
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(GITHUB_API_TIMEOUT_SECS))
+        .user_agent(concat!("vana/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("无法创建 HTTP 客户端")?;
+    let mut request = client
+        .get(format!("{}/repos/{}/{}/git/trees/{}?recursive=1", GITHUB_API_BASE, owner, repo, commit_sha))
+        .header("Accept", "application/vnd.github+json");
+    if let Some(pat) = pat {
+        request = request.bearer_auth(pat);
+    }
+    let response = request.send().await.context("无法连接 GitHub API")?;
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("GitHub API 返回错误: {}", status);
+    }
+
+    let body: TreeResponse = response.json().await.context("无法解析 GitHub API 响应")?;
+    if body.truncated {
+        tracing::warn!("[GitHub] {}/{}: 文件树过大，返回结果被截断，流量预估偏小", owner, repo);
+    }
+    Ok(Some(
+        body.tree
+            .into_iter()
+            .filter(|item| item.kind == "blob")
+            .map(|item| (item.sha, item.size.unwrap_or(0)))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_github_repo() {
+        let expected = Some(("alice".to_string(), "notes".to_string()));
+        assert_eq!(parse_github_repo("https://github.com/alice/notes.git"), expected);
+        assert_eq!(parse_github_repo("https://github.com/alice/notes"), expected);
+        assert_eq!(parse_github_repo("https://ghp_token@github.com/alice/notes.git"), expected);
+        assert_eq!(parse_github_repo("git@github.com:alice/notes.git"), expected);
+        assert_eq!(parse_github_repo("https://gitlab.com/alice/notes.git"), None);
+        assert_eq!(parse_github_repo("https://github.com/alice"), None);
+    }
+}
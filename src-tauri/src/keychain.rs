@@ -2,19 +2,102 @@
 // 负责生成、存储和检索主加密密钥
 // 密钥存储在系统 Keychain/Keystore 中，确保安全性
 
+use crate::crypto::{
+    decrypt_bytes, derive_key_from_passphrase, encrypt_bytes, generate_salt, PASSPHRASE_SALT_LEN,
+};
 use anyhow::{Context, Result};
 use rand::RngCore;
 use base64::Engine;
+use std::sync::Mutex;
 use tauri::AppHandle;
 
+// 主密钥恢复码格式：MAGIC (4) + VERSION (1) + SALT (16) + nonce + AES-256-GCM(主密钥)
+const RECOVERY_MAGIC: &[u8; 4] = b"VNKY";
+const RECOVERY_VERSION: u8 = 1;
+const RECOVERY_HEADER_LEN: usize = 4 + 1 + PASSPHRASE_SALT_LEN;
+
 // 密钥存储键名
 const MASTER_KEY_STORE_KEY: &str = "master_encryption_key";
 const PAT_TOKEN_STORE_KEY: &str = "github_pat_token";
 
+/// 工作区的主密钥存储键名（每个工作区 ID 一个键，保证不同工作区的加密相互独立）
+fn master_key_store_key(vault_id: &str) -> String {
+    format!("{}:{}", MASTER_KEY_STORE_KEY, vault_id)
+}
+
+// 当前激活工作区的主密钥缓存，避免每次文件读写都重新打开 vault_keys.json 并解码。
+// 切换工作区或更换密钥时必须调用 invalidate_master_key_cache。
+static MASTER_KEY_CACHE: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+/// 清除主密钥缓存（切换工作区、更换或重置密钥后调用）
+pub fn invalidate_master_key_cache() {
+    *MASTER_KEY_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+}
+
+/// 存储中的主密钥无法解码（不是 base64 或长度不是 32 字节）
+/// 
+/// 此时绝不能自动生成新密钥，否则所有已有的 .enc 文件将永久无法解密；
+/// 只能由用户通过 `reset_master_key` 明确重置
+#[derive(Debug, thiserror::Error)]
+#[error("存储的主密钥已损坏，已有的加密文件无法解密；如确认放弃这些文件，请重置主密钥")]
+pub struct CorruptMasterKey;
+
+/// 解码存储中的主密钥（base64 编码的 32 字节）
+fn decode_master_key(value: &serde_json::Value) -> Option<Vec<u8>> {
+    let key_bytes = base64::engine::general_purpose::STANDARD
+        .decode(value.as_str()?)
+        .ok()?;
+    (key_bytes.len() == 32).then_some(key_bytes)
+}
+
+/// 解析存储中的密钥条目：不存在时返回 None，存在但无法解码时返回 `CorruptMasterKey`
+fn parse_stored_master_key(value: Option<&serde_json::Value>) -> Result<Option<Vec<u8>>> {
+    match value {
+        None => Ok(None),
+        Some(value) => decode_master_key(value)
+            .map(Some)
+            .ok_or_else(|| CorruptMasterKey.into()),
+    }
+}
+
+/// 生成并保存新的主密钥
+fn store_new_master_key(store: &tauri_plugin_store::Store<tauri::Wry>, store_key: &str) -> Result<Vec<u8>> {
+    let mut key = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    store_master_key(store, store_key, &key)?;
+    Ok(key)
+}
+
+/// 保存主密钥
+fn store_master_key(store: &tauri_plugin_store::Store<tauri::Wry>, store_key: &str, key: &[u8]) -> Result<()> {
+    // 将密钥编码为 base64 并存储
+    let key_base64 = base64::engine::general_purpose::STANDARD.encode(key);
+    store.set(store_key, serde_json::json!(key_base64));
+    
+    // tauri-plugin-store v2 的 save() 是同步方法
+    store.save()?;
+
+    Ok(())
+}
+
+/// 覆盖密钥条目前，将原条目以 `<键名>:<原因>:<时间戳>` 保留在存储中以便人工恢复
+fn backup_key_entry(store: &tauri_plugin_store::Store<tauri::Wry>, store_key: &str, reason: &str) {
+    if let Some(previous) = store.get(store_key) {
+        let backup_key = format!("{}:{}:{}", store_key, reason, chrono::Utc::now().timestamp());
+        store.set(backup_key.clone(), previous);
+        tracing::warn!("[Keychain] 原主密钥条目已备份为 {}", backup_key);
+    }
+}
+
 /// 获取或创建主加密密钥
 /// 
 /// 如果密钥不存在，则生成一个新的 32 字节密钥并存储
 /// 如果密钥已存在，则从存储中读取
+/// 密钥按当前激活工作区的 ID 分别存储；旧版本保存在全局键下的密钥
+/// 在首次读取默认工作区密钥时迁移过来（全局键保留，便于回退到旧版本）
+/// 读取后缓存在内存中，后续调用不再访问存储
 /// 
 /// # 参数
 /// - `app`: Tauri 应用句柄，用于访问插件存储
@@ -22,6 +105,21 @@ const PAT_TOKEN_STORE_KEY: &str = "github_pat_token";
 /// # 返回
 /// 返回 32 字节的主加密密钥
 pub async fn get_or_create_master_key(app: &AppHandle) -> Result<Vec<u8>> {
+    // 读取过程本身没有 await，直接复用同步版本
+    get_or_create_master_key_sync(app)
+}
+
+/// 从存储读取当前工作区的主密钥，不存在时生成并保存
+fn load_or_create_master_key(app: &AppHandle) -> Result<Vec<u8>> {
+    // 无法确定当前工作区时直接报错，不能退回到其他工作区的密钥
+    let vault_id = crate::vaults::active_vault(app)
+        .context("无法确定当前工作区")?
+        .id;
+    load_or_create_vault_key(app, &vault_id)
+}
+
+/// 从存储读取指定工作区的主密钥，不存在时生成并保存
+fn load_or_create_vault_key(app: &AppHandle, vault_id: &str) -> Result<Vec<u8>> {
     // 使用 tauri-plugin-store 访问安全存储
     use tauri_plugin_store::StoreBuilder;
     use std::path::PathBuf;
@@ -31,40 +129,214 @@ pub async fn get_or_create_master_key(app: &AppHandle) -> Result<Vec<u8>> {
         PathBuf::from("vault_keys.json"),
     )
     .build()?;
+    if let Some(key_bytes) = load_stored_vault_key(&store, vault_id)? {
+        return Ok(key_bytes);
+    }
+
+    // 只有在从未保存过密钥时才生成新密钥
+    tracing::info!("[Keychain] 为工作区 {} 生成新的主密钥", vault_id);
+    store_new_master_key(&store, &master_key_store_key(vault_id))
+}
+
+/// 从存储读取指定工作区的主密钥；从未保存过时返回 None
+fn load_stored_vault_key<R: tauri::Runtime>(
+    store: &tauri_plugin_store::Store<R>,
+    vault_id: &str,
+) -> Result<Option<Vec<u8>>> {
+    let store_key = master_key_store_key(vault_id);
 
-    // 尝试读取现有密钥
-    if let Some(value) = store.get(MASTER_KEY_STORE_KEY) {
-        if let Some(key_str) = value.as_str() {
-            // 从 base64 字符串解码
-            if let Ok(key_bytes) = base64::engine::general_purpose::STANDARD.decode(key_str) {
-                if key_bytes.len() == 32 {
-                    return Ok(key_bytes);
-                }
+    // 尝试读取现有密钥；条目存在但已损坏时报错，不能生成新密钥覆盖
+    if let Some(key_bytes) = parse_stored_master_key(store.get(&store_key).as_ref())? {
+        return Ok(Some(key_bytes));
+    }
+
+    // 迁移：单工作区时代的密钥属于默认工作区
+    if vault_id == crate::vaults::DEFAULT_VAULT_ID {
+        if let Some(value) = store.get(MASTER_KEY_STORE_KEY) {
+            if let Some(key_bytes) = parse_stored_master_key(Some(&value))? {
+                store.set(store_key, value);
+                store.save()?;
+                tracing::info!("[Keychain] 已将旧版主密钥迁移到默认工作区");
+                return Ok(Some(key_bytes));
             }
         }
     }
+    Ok(None)
+}
 
-    // 如果密钥不存在或无效，生成新密钥
-    let mut key = vec![0u8; 32];
-    rand::thread_rng().fill_bytes(&mut key);
+/// 重置当前工作区的主密钥（仅用于存储的密钥损坏后的恢复）
+/// 
+/// 原有的密钥条目会以 `<键名>:corrupt:<时间戳>` 保留在存储中以便人工恢复，随后生成新密钥。
+/// 重置后，用旧密钥加密的文件都无法再解密，因此存储的密钥仍能解密工作区时拒绝重置。
+/// 
+/// # 参数
+/// - `app`: Tauri 应用句柄，用于访问插件存储
+pub fn reset_master_key(app: &AppHandle) -> Result<()> {
+    use tauri_plugin_store::StoreBuilder;
+    use std::path::PathBuf;
 
-    // 将密钥编码为 base64 并存储
-    let key_base64 = base64::engine::general_purpose::STANDARD.encode(&key);
-    store.set(MASTER_KEY_STORE_KEY.to_string(), serde_json::json!(key_base64));
-    
-    // tauri-plugin-store v2 的 save() 是同步方法
-    store.save()?;
+    let mut cache = MASTER_KEY_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let store = StoreBuilder::new(app, PathBuf::from("vault_keys.json")).build()?;
+    let vault = crate::vaults::active_vault(app)
+        .context("无法确定当前工作区")?;
+    let store_key = master_key_store_key(&vault.id);
+
+    if let Some(current) = parse_stored_master_key(store.get(&store_key).as_ref()).ok().flatten() {
+        if crate::storage::can_decrypt_vault(std::path::Path::new(&vault.path), &current) {
+            anyhow::bail!("当前的主密钥仍能解密工作区的文件，无需重置");
+        }
+    }
+
+    backup_key_entry(&store, &store_key, "corrupt");
+    store_new_master_key(&store, &store_key)?;
+    *cache = None;
 
+    tracing::warn!("[Keychain] 已重置工作区 {} 的主密钥", vault.id);
+    Ok(())
+}
+
+/// 用口令包装主密钥，生成可离线保存的恢复码
+/// 
+/// 格式：base64(MAGIC (4) + VERSION (1) + SALT (16) + nonce + AES-256-GCM(主密钥))，
+/// 包装密钥由口令经 Argon2id 派生
+fn wrap_master_key(key: &[u8], passphrase: &str) -> Result<String> {
+    let salt = generate_salt();
+    let wrapping_key = derive_key_from_passphrase(passphrase, &salt)?;
+    let ciphertext = encrypt_bytes(key, &wrapping_key).context("加密主密钥失败")?;
+
+    let mut blob = Vec::with_capacity(RECOVERY_HEADER_LEN + ciphertext.len());
+    blob.extend_from_slice(RECOVERY_MAGIC);
+    blob.push(RECOVERY_VERSION);
+    blob.extend_from_slice(&salt);
+    blob.extend_from_slice(&ciphertext);
+    Ok(base64::engine::general_purpose::STANDARD.encode(blob))
+}
+
+/// 用口令解开恢复码，返回 32 字节主密钥
+fn unwrap_master_key(blob: &str, passphrase: &str) -> Result<Vec<u8>> {
+    let blob = base64::engine::general_purpose::STANDARD
+        .decode(blob.trim())
+        .context("恢复码格式无效")?;
+    if blob.len() <= RECOVERY_HEADER_LEN || &blob[..4] != RECOVERY_MAGIC {
+        anyhow::bail!("恢复码格式无效");
+    }
+    if blob[4] != RECOVERY_VERSION {
+        anyhow::bail!("不支持的恢复码版本: {}", blob[4]);
+    }
+
+    let salt = &blob[5..RECOVERY_HEADER_LEN];
+    let wrapping_key = derive_key_from_passphrase(passphrase, salt)?;
+    let key = decrypt_bytes(&blob[RECOVERY_HEADER_LEN..], &wrapping_key)
+        .map_err(|_| anyhow::anyhow!("口令错误或恢复码已损坏"))?;
+    if key.len() != 32 {
+        anyhow::bail!("恢复码中的密钥长度无效");
+    }
     Ok(key)
 }
 
+/// 导出当前工作区的主密钥恢复码（用口令包装，可离线保存）
+/// 
+/// # 参数
+/// - `app`: Tauri 应用句柄
+/// - `passphrase`: 保护恢复码的口令（恢复时需要）
+/// 
+/// # 返回
+/// 返回 base64 编码的恢复码
+pub fn export_master_key(app: &AppHandle, passphrase: &str) -> Result<String> {
+    let key = get_or_create_master_key_sync(app)?;
+    let blob = wrap_master_key(&key, passphrase)?;
+    tracing::info!("[Keychain] 已导出主密钥恢复码");
+    Ok(blob)
+}
+
+/// 从恢复码恢复当前工作区的主密钥（例如重装应用或换设备后）
+/// 
+/// 保存前先用恢复的密钥试解密工作区中的文件，密钥不匹配时拒绝导入，避免覆盖正确的密钥；
+/// 当前的密钥条目会先备份在存储中，再被恢复的密钥覆盖
+/// 
+/// # 参数
+/// - `app`: Tauri 应用句柄
+/// - `blob`: `export_master_key` 生成的恢复码
+/// - `passphrase`: 导出时使用的口令
+pub fn import_master_key(app: &AppHandle, blob: &str, passphrase: &str) -> Result<()> {
+    use tauri_plugin_store::StoreBuilder;
+    use std::path::PathBuf;
+
+    let key = unwrap_master_key(blob, passphrase)?;
+    let workspace_path = crate::commands::get_workspace_path(app.clone())
+        .map_err(|e| anyhow::anyhow!(e))?;
+    if !crate::storage::can_decrypt_vault(std::path::Path::new(&workspace_path), &key) {
+        anyhow::bail!("恢复码中的密钥无法解密当前工作区的文件，请确认恢复码属于这个工作区");
+    }
+
+    let mut cache = MASTER_KEY_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let store = StoreBuilder::new(app, PathBuf::from("vault_keys.json")).build()?;
+    let vault_id = crate::vaults::active_vault(app)
+        .context("无法确定当前工作区")?
+        .id;
+    let store_key = master_key_store_key(&vault_id);
+
+    if parse_stored_master_key(store.get(&store_key).as_ref()).ok().flatten().as_deref() != Some(key.as_slice()) {
+        backup_key_entry(&store, &store_key, "replaced");
+    }
+    store_master_key(&store, &store_key, &key)?;
+    *cache = None;
+
+    tracing::info!("[Keychain] 已从恢复码恢复工作区 {} 的主密钥", vault_id);
+    Ok(())
+}
+
 /// 同步版本的密钥获取（用于非异步上下文）
 /// 
-/// 注意：这会在后台线程中执行异步操作
+/// 优先返回缓存的密钥；缓存未命中时直接同步读取存储，不会创建新的 Tokio runtime，
+/// 因此在已运行于 runtime 上的 Tauri 命令中调用也是安全的
 pub fn get_or_create_master_key_sync(app: &AppHandle) -> Result<Vec<u8>> {
-    // 使用 tokio runtime 执行异步操作
-    let rt = tokio::runtime::Runtime::new().context("无法创建 Tokio runtime")?;
-    rt.block_on(get_or_create_master_key(app))
+    // 加载期间持有缓存锁，并发的首次调用不会各自生成不同的新密钥
+    let mut cache = MASTER_KEY_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(key) = cache.as_ref() {
+        return Ok(key.clone());
+    }
+
+    let key = load_or_create_master_key(app)?;
+    *cache = Some(key.clone());
+    Ok(key)
+}
+
+/// 读取指定工作区已保存的主密钥（用于在工作区之间移动文件），不会生成新密钥
+/// 
+/// 当前激活的工作区优先使用缓存。本机从未保存过该工作区的密钥时返回错误
+/// （例如克隆的工作区还没有导入密钥）：用临时生成的密钥加密的笔记将无法在其他设备上解密。
+/// 
+/// # 参数
+/// - `app`: Tauri 应用句柄，用于访问插件存储
+/// - `vault_id`: 工作区 ID
+pub fn load_vault_key(app: &AppHandle, vault_id: &str) -> Result<Vec<u8>> {
+    use tauri_plugin_store::StoreBuilder;
+    use std::path::PathBuf;
+
+    let active_id = crate::vaults::active_vault(app)
+        .context("无法确定当前工作区")?
+        .id;
+    if active_id == vault_id {
+        let cache = MASTER_KEY_CACHE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(key) = cache.as_ref() {
+            return Ok(key.clone());
+        }
+    }
+
+    let store = StoreBuilder::new(app, PathBuf::from("vault_keys.json")).build()?;
+    load_stored_vault_key(&store, vault_id)?
+        .ok_or_else(|| anyhow::anyhow!("本机没有工作区 {} 的主密钥，请先导入该工作区的密钥", vault_id))
 }
 
 /// 存储 GitHub PAT Token
@@ -114,18 +386,86 @@ pub async fn get_pat_token(app: &AppHandle) -> Result<Option<String>> {
     .build()?;
     
     // 尝试读取存储的 PAT
-    if let Some(value) = store.get(PAT_TOKEN_STORE_KEY) {
-        if let Some(token_base64) = value.as_str() {
-            // 从 base64 解码
-            if let Ok(token_bytes) = base64::engine::general_purpose::STANDARD.decode(token_base64) {
-                if let Ok(token) = String::from_utf8(token_bytes) {
-                    return Ok(Some(token));
-                }
-            }
-        }
-    }
-    
-    Ok(None)
+    Ok(read_pat_entry(&store, PAT_TOKEN_STORE_KEY))
+}
+
+/// 读取 base64 编码存储的 PAT 条目；不存在或无法解码时返回 None
+fn read_pat_entry(store: &tauri_plugin_store::Store<tauri::Wry>, store_key: &str) -> Option<String> {
+    let value = store.get(store_key)?;
+    let token_bytes = base64::engine::general_purpose::STANDARD
+        .decode(value.as_str()?)
+        .ok()?;
+    String::from_utf8(token_bytes).ok()
+}
+
+/// 从远程 URL 中取出主机名（小写），支持 `https://user@host:port/path` 和 `git@host:path`
+fn remote_host(remote_url: &str) -> Option<String> {
+    let url = remote_url.trim();
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest,
+        // 没有协议时只有 scp 风格的 `user@host:path` 才是远程主机，其余是本地路径
+        None if url.contains('@') => url,
+        None => return None,
+    };
+    let authority = rest.split('/').next()?;
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    // URL 中冒号后是端口，scp 风格中冒号后是路径，两种情况都只取冒号前的部分
+    let host = host_port.split(':').next()?.to_ascii_lowercase();
+    (!host.is_empty()).then_some(host)
+}
+
+/// 远程对应的 PAT 存储键名（按主机区分；无法解析主机时使用整个 URL）
+fn pat_store_key_for(remote_url: &str) -> String {
+    let scope = remote_host(remote_url).unwrap_or_else(|| remote_url.trim().to_ascii_lowercase());
+    format!("{}:{}", PAT_TOKEN_STORE_KEY, scope)
+}
+
+/// 为指定远程（按主机区分）存储 PAT，例如 GitHub 和自建备份服务器各用各的令牌
+/// 
+/// # 参数
+/// - `app`: Tauri 应用句柄
+/// - `remote_url`: 远程仓库 URL
+/// - `token`: PAT Token 字符串
+pub fn store_pat_for(app: &AppHandle, remote_url: &str, token: &str) -> Result<()> {
+    use tauri_plugin_store::StoreBuilder;
+    use std::path::PathBuf;
+
+    let store = StoreBuilder::new(app, PathBuf::from("vault_keys.json")).build()?;
+    let token_base64 = base64::engine::general_purpose::STANDARD.encode(token.as_bytes());
+    store.set(pat_store_key_for(remote_url), serde_json::json!(token_base64));
+    store.save()?;
+    Ok(())
+}
+
+/// 获取指定远程使用的 PAT
+/// 
+/// 没有为该主机单独存储令牌时，退回到旧版本保存的全局令牌
+/// 
+/// # 参数
+/// - `app`: Tauri 应用句柄
+/// - `remote_url`: 远程仓库 URL
+pub fn get_pat_for(app: &AppHandle, remote_url: &str) -> Result<Option<String>> {
+    use tauri_plugin_store::StoreBuilder;
+    use std::path::PathBuf;
+
+    let store = StoreBuilder::new(app, PathBuf::from("vault_keys.json")).build()?;
+    Ok(read_pat_entry(&store, &pat_store_key_for(remote_url))
+        .or_else(|| read_pat_entry(&store, PAT_TOKEN_STORE_KEY)))
+}
+
+/// 删除为指定远程单独存储的 PAT（全局令牌不受影响）
+/// 
+/// # 参数
+/// - `app`: Tauri 应用句柄
+/// - `remote_url`: 远程仓库 URL
+pub fn remove_pat_for(app: &AppHandle, remote_url: &str) -> Result<()> {
+    use tauri_plugin_store::StoreBuilder;
+    use std::path::PathBuf;
+
+    let store = StoreBuilder::new(app, PathBuf::from("vault_keys.json")).build()?;
+    store.delete(pat_store_key_for(remote_url));
+    store.save()?;
+    Ok(())
 }
 
 /// 删除 GitHub PAT Token
@@ -168,5 +508,47 @@ pub async fn has_pat_token(app: &AppHandle) -> Result<bool> {
 mod tests {
     // 注意：这些测试需要实际的 Tauri 应用上下文，在单元测试中可能无法运行
     // 实际测试应该在集成测试中进行
+    use super::*;
+
+    #[test]
+    fn test_corrupt_stored_key_is_an_error() {
+        let valid = serde_json::json!(base64::engine::general_purpose::STANDARD.encode([7u8; 32]));
+        assert_eq!(parse_stored_master_key(Some(&valid)).unwrap(), Some(vec![7u8; 32]));
+        assert_eq!(parse_stored_master_key(None).unwrap(), None);
+
+        let short = serde_json::json!(base64::engine::general_purpose::STANDARD.encode([7u8; 16]));
+        for corrupt in [short, serde_json::json!("not base64!"), serde_json::json!(42)] {
+            let err = parse_stored_master_key(Some(&corrupt)).unwrap_err();
+            assert!(err.downcast_ref::<CorruptMasterKey>().is_some());
+        }
+    }
+
+    #[test]
+    fn test_master_key_recovery_round_trip() {
+        let key = [5u8; 32];
+        let blob = wrap_master_key(&key, "correct horse").unwrap();
+        assert_eq!(unwrap_master_key(&blob, "correct horse").unwrap(), key.to_vec());
+        assert!(unwrap_master_key(&blob, "wrong").is_err());
+        assert!(unwrap_master_key("bm90IGEgYmxvYg==", "correct horse").is_err());
+        assert!(wrap_master_key(&key, "").is_err());
+    }
+
+    #[test]
+    fn test_pat_store_key_is_scoped_by_remote_host() {
+        assert_eq!(remote_host("https://github.com/me/vault.git").as_deref(), Some("github.com"));
+        assert_eq!(remote_host("https://x-access-token@GitHub.com/me/vault.git").as_deref(), Some("github.com"));
+        assert_eq!(remote_host("https://git.example.org:8443/me/vault.git").as_deref(), Some("git.example.org"));
+        assert_eq!(remote_host("git@git.example.org:me/vault.git").as_deref(), Some("git.example.org"));
+        assert_eq!(remote_host("/srv/git/vault.git"), None);
+
+        assert_eq!(
+            pat_store_key_for("https://github.com/me/a.git"),
+            pat_store_key_for("https://github.com/me/b.git")
+        );
+        assert_ne!(
+            pat_store_key_for("https://github.com/me/a.git"),
+            pat_store_key_for("https://git.example.org/me/a.git")
+        );
+    }
 }
 
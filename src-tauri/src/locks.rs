@@ -0,0 +1,188 @@
+// No Visitors - 并发锁模块
+// 自动提交定时器、用户保存和同步 checkout 可能同时写入同一个 .enc 文件，
+// 交错写入会破坏 AES-GCM 帧。这里提供按路径的异步互斥锁，保证同一路径上的操作串行执行。
+// 同时提供仓库级互斥锁，串行化所有会修改 .git（index、HEAD、refs）的 Git 操作。
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::{MutexGuard, OwnedMutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+thread_local! {
+    // 当前线程是否为 `spawn_blocking` 启动的阻塞线程（可以阻塞等待工作区锁）
+    static IN_BLOCKING_POOL: Cell<bool> = const { Cell::new(false) };
+}
+
+type PathLockMap = HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>;
+
+static PATH_LOCKS: OnceLock<Mutex<PathLockMap>> = OnceLock::new();
+
+// 工作区闸门：单文件操作持有读锁（彼此并行），checkout 等整树操作持有写锁（独占）
+static WORKSPACE_GATE: RwLock<()> = RwLock::const_new(());
+
+// 仓库锁：提交、同步、推送、窗口关闭时的清仓同步等修改 .git 的操作必须互斥
+static REPO_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// 单文件操作持有的锁（工作区读锁 + 路径互斥锁）
+pub struct PathLock {
+    _path_guards: Vec<OwnedMutexGuard<()>>,
+    _gate: RwLockReadGuard<'static, ()>,
+}
+
+/// 规范化锁的键：文件可能尚不存在，因此规范化其父目录后再拼接文件名
+fn lock_key(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|p| p.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn path_mutex(key: PathBuf) -> Arc<tokio::sync::Mutex<()>> {
+    let mut locks = PATH_LOCKS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    // 清理已无人持有的锁，避免表无限增长
+    locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+
+    locks
+        .entry(key)
+        .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+        .clone()
+}
+
+/// 获取一组路径的锁（例如重命名时的源路径和目标路径）
+///
+/// 按规范化路径排序后依次加锁，避免两个操作以相反顺序加锁导致死锁。
+///
+/// # 参数
+/// - `paths`: 需要串行化的文件或目录路径
+///
+/// # 返回
+/// 返回锁守卫，离开作用域时释放
+pub async fn lock_paths(paths: &[&Path]) -> PathLock {
+    let gate = WORKSPACE_GATE.read().await;
+
+    let mut keys: Vec<PathBuf> = paths.iter().map(|p| lock_key(p)).collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut path_guards = Vec::with_capacity(keys.len());
+    for key in keys {
+        path_guards.push(path_mutex(key).lock_owned().await);
+    }
+
+    PathLock {
+        _path_guards: path_guards,
+        _gate: gate,
+    }
+}
+
+/// 获取单个路径的锁
+pub async fn lock_path(path: &Path) -> PathLock {
+    lock_paths(&[path]).await
+}
+
+/// 独占整个工作区（异步上下文使用，例如移动整个目录）
+pub async fn lock_workspace() -> RwLockWriteGuard<'static, ()> {
+    WORKSPACE_GATE.write().await
+}
+
+/// 独占整个工作区（用于 checkout 等会批量改写工作区文件的 Git 操作）
+///
+/// 注意：这是阻塞调用，只能在同步上下文（同步 Tauri 命令、`spawn_blocking`）中使用，
+/// 不能在 async 任务中调用；async 代码使用 `lock_workspace().await`。
+pub fn lock_workspace_blocking() -> RwLockWriteGuard<'static, ()> {
+    debug_assert!(
+        tokio::runtime::Handle::try_current().is_err() || IN_BLOCKING_POOL.with(Cell::get),
+        "lock_workspace_blocking 不能在 tokio 工作线程上调用，请使用 lock_workspace().await"
+    );
+    WORKSPACE_GATE.blocking_write()
+}
+
+/// 在阻塞线程池中执行同步操作（其中可以调用 `lock_workspace_blocking` 等阻塞加锁函数）
+///
+/// 阻塞线程同样处于 tokio 运行时上下文中，需经由此函数启动，`lock_workspace_blocking`
+/// 才能把它与 tokio 工作线程区分开。
+pub fn spawn_blocking<T, F>(operation: F) -> tokio::task::JoinHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        IN_BLOCKING_POOL.with(|flag| flag.set(true));
+        operation()
+    })
+}
+
+/// 获取仓库锁（异步上下文使用）
+///
+/// 修改 .git 的 Git 操作（commit / fetch / push / sync / checkout 等）在执行前必须持有此锁，
+/// 例如自动提交不能在同步进行到一半时插入。
+pub async fn lock_repo() -> MutexGuard<'static, ()> {
+    REPO_LOCK.lock().await
+}
+
+/// 获取仓库锁（同步上下文使用）
+///
+/// 注意：这是阻塞调用，只能在同步 Tauri 命令等非 async 上下文中使用。
+pub fn lock_repo_blocking() -> MutexGuard<'static, ()> {
+    REPO_LOCK.blocking_lock()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_same_path_is_serialized() {
+        let dir = crate::test_util::temp_workspace("lock_same_path");
+        let path = dir.join("note.md.enc");
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(AtomicUsize::new(0));
+
+        let mut tasks = Vec::new();
+        for _ in 0..8 {
+            let path = path.clone();
+            let active = active.clone();
+            let max_active = max_active.clone();
+            tasks.push(tokio::spawn(async move {
+                let _lock = lock_path(&path).await;
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                max_active.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(max_active.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_workspace_lock_excludes_path_locks() {
+        let dir = crate::test_util::temp_workspace("lock_workspace_gate");
+        let path = dir.join("note.md.enc");
+        let workspace = lock_workspace().await;
+        let waiting = tokio::spawn(async move {
+            let _lock = lock_path(&path).await;
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished(), "独占工作区期间不能获取路径锁");
+
+        drop(workspace);
+        waiting.await.unwrap();
+    }
+}
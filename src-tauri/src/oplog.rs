@@ -0,0 +1,64 @@
+// No Visitors - 操作日志模块
+// 记录每次 Git 操作前后分支指向的提交（类似 reflog），保存在 .git 目录下，不会被提交或同步。
+// 同步、合并等操作出错时，可以据此把分支恢复到操作之前的位置。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// 操作日志文件（位于 .git 下）
+const OPLOG_FILE: &str = "vana_oplog.json";
+// 只保留最近的记录，避免日志无限增长
+const MAX_OPLOG_ENTRIES: usize = 200;
+
+/// 一次移动分支的操作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpLogEntry {
+    pub operation: String,      // 操作名称，例如 "commit"、"sync"、"merge"
+    pub refname: String,        // 被移动的引用，例如 "refs/heads/main"
+    pub before: Option<String>, // 操作前的提交（分支尚未创建时为 None）
+    pub after: String,          // 操作后的提交
+    pub timestamp: String,      // RFC 3339
+    #[serde(default)]
+    pub undone: bool,           // 是否已被撤销（撤销操作本身也标记为 true，不会被再次撤销）
+}
+
+fn oplog_path(repo_path: &Path) -> PathBuf {
+    repo_path.join(".git").join(OPLOG_FILE)
+}
+
+/// 读取操作日志（按时间从旧到新）；日志不存在时返回空列表
+pub fn read_oplog(repo_path: &Path) -> Result<Vec<OpLogEntry>> {
+    let path = oplog_path(repo_path);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("无法读取操作日志: {}", path.display())),
+    };
+    serde_json::from_str(&content).context("操作日志格式无效")
+}
+
+/// 覆盖写入操作日志（先写临时文件再重命名，避免写到一半时损坏）
+pub fn write_oplog(repo_path: &Path, entries: &[OpLogEntry]) -> Result<()> {
+    let path = oplog_path(repo_path);
+    let start = entries.len().saturating_sub(MAX_OPLOG_ENTRIES);
+    let content = serde_json::to_string_pretty(&entries[start..])
+        .context("无法序列化操作日志")?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, content)
+        .with_context(|| format!("无法写入操作日志: {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path)
+        .with_context(|| format!("无法写入操作日志: {}", path.display()))?;
+    Ok(())
+}
+
+/// 追加一条记录
+pub fn append_oplog(repo_path: &Path, entry: OpLogEntry) -> Result<()> {
+    // 日志损坏时重新开始记录，不能因为日志问题阻止 Git 操作
+    let mut entries = read_oplog(repo_path).unwrap_or_else(|e| {
+        tracing::warn!("[OpLog] {}，将重新开始记录", e);
+        Vec::new()
+    });
+    entries.push(entry);
+    write_oplog(repo_path, &entries)
+}
@@ -0,0 +1,354 @@
+// No Visitors - 全文搜索索引模块
+// search_files 每次都要解密整个工作区，笔记多时很慢。这里维护一个持久化的倒排索引（词 -> 文件），
+// 用主密钥加密后保存在 .git 目录下（只在本地，不会被提交或同步，随时可以重建）。
+// 搜索时先用索引筛出候选文件，只解密候选文件确认匹配位置，结果与 search_files 一致。
+
+use crate::crypto::{decrypt_bytes, encrypt_bytes};
+use crate::keychain::get_or_create_master_key;
+use crate::storage::{
+    collect_note_files, decrypt_all_with_limit, decrypt_concurrency, search_content,
+    workspace_relative_path, SearchResult,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+// 索引文件（位于 .git 下）
+const SEARCH_INDEX_FILE: &str = "vana_search_index.enc";
+// 索引格式版本，格式变化时旧索引直接丢弃重建
+const SEARCH_INDEX_VERSION: u32 = 1;
+
+// 加载、更新、保存索引必须串行，否则并发搜索会互相覆盖索引文件
+static INDEX_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// 文件的大小和修改时间，变化时重新索引该文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> Option<Fingerprint> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?;
+        Some(Fingerprint {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+/// 倒排索引
+#[derive(Debug, Serialize, Deserialize)]
+struct SearchIndex {
+    version: u32,
+    files: BTreeMap<String, Fingerprint>,          // 已索引的文件（相对路径）
+    postings: BTreeMap<String, BTreeSet<String>>, // 词（小写）-> 包含该词的文件
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        SearchIndex {
+            version: SEARCH_INDEX_VERSION,
+            files: BTreeMap::new(),
+            postings: BTreeMap::new(),
+        }
+    }
+}
+
+impl SearchIndex {
+    fn remove_file(&mut self, path: &str) {
+        if self.files.remove(path).is_none() {
+            return;
+        }
+        self.postings.retain(|_, files| {
+            files.remove(path);
+            !files.is_empty()
+        });
+    }
+
+    fn insert_file(&mut self, path: String, fingerprint: Fingerprint, tokens: BTreeSet<String>) {
+        self.remove_file(&path);
+        for token in tokens {
+            self.postings.entry(token).or_default().insert(path.clone());
+        }
+        self.files.insert(path, fingerprint);
+    }
+
+    /// 可能包含 `query`（已转换为小写）的文件，按路径排序
+    ///
+    /// 查询中的每个词都必须是文件中某个词的子串；查询没有可索引的词时返回所有文件
+    fn candidates(&self, query: &str) -> Vec<String> {
+        let mut candidates: Option<BTreeSet<String>> = None;
+        for term in tokenize(query) {
+            let files: BTreeSet<String> = self
+                .postings
+                .iter()
+                .filter(|(token, _)| token.contains(term.as_str()))
+                .flat_map(|(_, files)| files.iter().cloned())
+                .collect();
+            candidates = Some(match candidates {
+                Some(current) => current.intersection(&files).cloned().collect(),
+                None => files,
+            });
+        }
+        match candidates {
+            Some(candidates) => candidates.into_iter().collect(),
+            None => self.files.keys().cloned().collect(),
+        }
+    }
+}
+
+/// 把内容拆分为小写的词（连续的字母/数字，包括中文等文字）
+fn tokenize(content: &str) -> BTreeSet<String> {
+    content
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_string())
+        .collect()
+}
+
+fn index_path(workspace: &Path) -> PathBuf {
+    workspace.join(".git").join(SEARCH_INDEX_FILE)
+}
+
+/// 读取索引；不存在、无法解密（例如主密钥已更换）或版本不符时返回 None
+fn load_index(workspace: &Path, key: &[u8]) -> Option<SearchIndex> {
+    let ciphertext = std::fs::read(index_path(workspace)).ok()?;
+    let index = decrypt_bytes(&ciphertext, key)
+        .ok()
+        .and_then(|plaintext| serde_json::from_slice::<SearchIndex>(&plaintext).ok());
+    match index {
+        Some(index) if index.version == SEARCH_INDEX_VERSION => Some(index),
+        _ => {
+            tracing::warn!("[SearchIndex] 索引无法读取或版本不符，将重建");
+            None
+        }
+    }
+}
+
+/// 加密保存索引（先写临时文件再重命名，避免写到一半时损坏）
+fn save_index(workspace: &Path, key: &[u8], index: &SearchIndex) -> Result<()> {
+    let path = index_path(workspace);
+    let plaintext = serde_json::to_vec(index).context("无法序列化搜索索引")?;
+    let ciphertext = encrypt_bytes(&plaintext, key).context("无法加密搜索索引")?;
+    let tmp = path.with_extension("enc.tmp");
+    std::fs::write(&tmp, ciphertext)
+        .with_context(|| format!("无法写入搜索索引: {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path)
+        .with_context(|| format!("无法写入搜索索引: {}", path.display()))?;
+    Ok(())
+}
+
+/// 增量更新索引：只重新解密新增或大小/修改时间变化的文件，并移除已删除的文件
+///
+/// # 返回
+/// 索引有变化时返回 true
+async fn update_index(workspace: &Path, key: &[u8], index: &mut SearchIndex) -> bool {
+    let current: BTreeMap<String, (PathBuf, Option<Fingerprint>)> = collect_note_files(workspace)
+        .into_iter()
+        .map(|path| {
+            let fingerprint = Fingerprint::of(&path);
+            (workspace_relative_path(workspace, &path), (path, fingerprint))
+        })
+        .collect();
+
+    let removed: Vec<String> = index
+        .files
+        .keys()
+        .filter(|path| !current.contains_key(*path))
+        .cloned()
+        .collect();
+    for path in &removed {
+        index.remove_file(path);
+    }
+
+    let stale: Vec<PathBuf> = current
+        .iter()
+        .filter(|(relative, (_, fingerprint))| fingerprint.is_none() || index.files.get(*relative) != fingerprint.as_ref())
+        .map(|(_, (path, _))| path.clone())
+        .collect();
+    if stale.is_empty() {
+        return !removed.is_empty();
+    }
+
+    let reindexed = stale.len();
+    let results = decrypt_all_with_limit(stale, decrypt_concurrency(), key, |file| {
+        tokenize(&String::from_utf8_lossy(&file.bytes))
+    })
+    .await;
+    for (path, tokens) in results {
+        let relative = workspace_relative_path(workspace, &path);
+        match (tokens, current.get(&relative).and_then(|(_, fingerprint)| *fingerprint)) {
+            (Some(tokens), Some(fingerprint)) => index.insert_file(relative, fingerprint, tokens),
+            // 无法解密的文件不进入索引，下次更新时重试
+            _ => index.remove_file(&relative),
+        }
+    }
+    tracing::info!("[SearchIndex] 已更新索引：重新索引 {} 个文件，移除 {} 个文件", reindexed, removed.len());
+    true
+}
+
+/// 重建结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIndexStats {
+    pub files: usize, // 已索引的文件数
+    pub terms: usize, // 索引中的词数
+}
+
+/// 丢弃现有索引，重新解密整个工作区建立索引
+///
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `app`: Tauri 应用句柄，用于获取密钥
+pub async fn rebuild_search_index(workspace_path: &str, app: &AppHandle) -> Result<SearchIndexStats> {
+    let workspace = Path::new(workspace_path);
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+
+    let _lock = INDEX_LOCK.lock().await;
+    let mut index = SearchIndex::default();
+    update_index(workspace, &master_key, &mut index).await;
+    save_index(workspace, &master_key, &index)?;
+    Ok(SearchIndexStats {
+        files: index.files.len(),
+        terms: index.postings.len(),
+    })
+}
+
+/// 提交后增量更新索引（只在索引已经建立过时更新，不会因为提交而触发首次全量建立）
+///
+/// 在后台执行，失败只记录警告
+pub fn refresh_search_index_in_background(app: AppHandle, workspace_path: String) {
+    tauri::async_runtime::spawn(async move {
+        let workspace = Path::new(&workspace_path);
+        if !index_path(workspace).exists() {
+            return;
+        }
+        let master_key = match get_or_create_master_key(&app).await {
+            Ok(key) => key,
+            Err(e) => {
+                tracing::warn!("[SearchIndex] 无法获取主加密密钥: {}", e);
+                return;
+            }
+        };
+
+        let _lock = INDEX_LOCK.lock().await;
+        let mut index = load_index(workspace, &master_key).unwrap_or_default();
+        if update_index(workspace, &master_key, &mut index).await {
+            if let Err(e) = save_index(workspace, &master_key, &index) {
+                tracing::warn!("[SearchIndex] 保存索引失败: {}", e);
+            }
+        }
+    });
+}
+
+/// 一页搜索结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchPage {
+    pub total: usize,                // 匹配的文件总数
+    pub results: Vec<SearchResult>, // 本页结果（按路径排序）
+}
+
+/// 用索引搜索（结果与 `search_files` 一致，支持分页）
+///
+/// 首次搜索时建立索引，之后每次只增量更新变化的文件，并只解密索引筛出的候选文件
+///
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `query`: 搜索关键词
+/// - `offset`: 跳过的结果数
+/// - `limit`: 本页最多返回的结果数
+/// - `app`: Tauri 应用句柄，用于获取密钥
+pub async fn search_indexed(
+    workspace_path: &str,
+    query: &str,
+    offset: usize,
+    limit: usize,
+    app: &AppHandle,
+) -> Result<SearchPage> {
+    let workspace = Path::new(workspace_path);
+    if query.is_empty() || !workspace.is_dir() {
+        return Ok(SearchPage { total: 0, results: Vec::new() });
+    }
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let query_lower = query.to_lowercase();
+
+    let candidates = {
+        let _lock = INDEX_LOCK.lock().await;
+        let mut index = load_index(workspace, &master_key).unwrap_or_default();
+        if update_index(workspace, &master_key, &mut index).await {
+            save_index(workspace, &master_key, &index)?;
+        }
+        index.candidates(&query_lower)
+    };
+
+    let paths = candidates.iter().map(|relative| workspace.join(relative)).collect();
+    let matches = decrypt_all_with_limit(paths, decrypt_concurrency(), &master_key, move |file| {
+        search_content(&String::from_utf8_lossy(&file.bytes), &query_lower)
+    })
+    .await;
+
+    let results: Vec<SearchResult> = matches
+        .into_iter()
+        .filter_map(|(path, matches)| {
+            let matches = matches.filter(|m| !m.is_empty())?;
+            Some(SearchResult {
+                file_path: workspace_relative_path(workspace, &path),
+                matches,
+            })
+        })
+        .collect();
+    let total = results.len();
+    Ok(SearchPage {
+        total,
+        results: results.into_iter().skip(offset).take(limit).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_workspace, write_note};
+
+    #[tokio::test]
+    async fn test_index_updates_incrementally_and_finds_candidates() {
+        let workspace = temp_workspace("search_index");
+        std::fs::create_dir_all(workspace.join(".git")).unwrap();
+        let key = [7u8; 32];
+        write_note(&workspace, "notes/a.md.enc", "Hello World", &key);
+        write_note(&workspace, "notes/b.md.enc", "旅行计划 hello", &key);
+        write_note(&workspace, "c.md.enc", "nothing here", &key);
+
+        let mut index = SearchIndex::default();
+        assert!(update_index(&workspace, &key, &mut index).await);
+        assert_eq!(index.files.len(), 3);
+        assert_eq!(index.candidates("ello"), vec!["notes/a.md.enc", "notes/b.md.enc"]);
+        assert_eq!(index.candidates("o w"), vec!["notes/a.md.enc"]);
+        assert_eq!(index.candidates("计划"), vec!["notes/b.md.enc"]);
+
+        save_index(&workspace, &key, &index).unwrap();
+        let mut index = load_index(&workspace, &key).unwrap();
+        assert!(load_index(&workspace, &[9u8; 32]).is_none());
+        assert!(!update_index(&workspace, &key, &mut index).await);
+
+        std::fs::remove_file(workspace.join("notes/a.md.enc")).unwrap();
+        write_note(&workspace, "c.md.enc", "hello again", &key);
+        assert!(update_index(&workspace, &key, &mut index).await);
+        assert_eq!(index.candidates("hello"), vec!["c.md.enc", "notes/b.md.enc"]);
+        assert!(!index.postings.contains_key("world"));
+    }
+}
@@ -2,13 +2,78 @@
 // 负责加密文件的读写操作
 // 所有文件都以 .enc 扩展名存储，内容使用 AES-256-GCM 加密
 
-use crate::crypto::{decrypt_content, encrypt_content};
+use crate::crypto::{decrypt_bytes, decrypt_note, decrypt_note_bytes, encrypt_note, encrypt_note_bytes, NoteMeta};
 use crate::keychain::get_or_create_master_key;
+use crate::locks::{lock_path, lock_paths, lock_workspace};
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 use tokio::fs;
 
+/// 校验路径位于工作区内（路径穿越防护）
+/// 
+/// 文件可能尚不存在（写入、重命名目标），因此规范化最近的已存在祖先目录后再拼接剩余部分；
+/// 剩余部分中出现 `..` 时无法可靠解析，直接拒绝。相对路径按工作区根目录解析。
+/// 
+/// # 参数
+/// - `workspace`: 工作区根目录
+/// - `path`: 待校验的路径
+/// 
+/// # 返回
+/// 返回规范化后的绝对路径；路径在工作区之外时返回错误
+pub fn confine_to_workspace(workspace: &Path, path: &Path) -> Result<PathBuf> {
+    let root = workspace
+        .canonicalize()
+        .with_context(|| format!("无法解析工作区路径: {}", workspace.display()))?;
+    let candidate = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        root.join(path)
+    };
+
+    let resolved = resolve_path(&candidate)
+        .with_context(|| format!("无法解析路径: {}", path.display()))?;
+    if !resolved.starts_with(&root) {
+        anyhow::bail!("路径不在工作区内: {}", path.display());
+    }
+    Ok(resolved)
+}
+
+/// 规范化路径（解析 `..` 和符号链接），路径可以尚不存在：规范化最近的已存在上级目录，再拼接剩余部分
+pub(crate) fn resolve_path(path: &Path) -> Result<PathBuf> {
+    let mut existing = path.to_path_buf();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match existing.file_name() {
+            Some(name) => {
+                rest.push(name.to_os_string());
+                existing.pop();
+            }
+            None => anyhow::bail!("无法解析路径: {}", path.display()),
+        }
+    }
+
+    let mut resolved = existing
+        .canonicalize()
+        .with_context(|| format!("无法解析路径: {}", path.display()))?;
+    for name in rest.iter().rev() {
+        resolved.push(name);
+    }
+    Ok(resolved)
+}
+
+/// 拒绝把目录移动/复制到它自身或它的子目录中（复制时会无限递归直到磁盘写满）
+fn ensure_not_into_itself(source: &Path, dest: &Path) -> Result<()> {
+    let source_canonical = source
+        .canonicalize()
+        .with_context(|| format!("无法解析源路径: {}", source.display()))?;
+    let dest_resolved = resolve_path(dest)?;
+    if source_canonical.is_dir() && dest_resolved.starts_with(&source_canonical) {
+        anyhow::bail!("不能将目录移动或复制到其自身或子目录中");
+    }
+    Ok(())
+}
+
 /// 读取并解密文件内容
 /// 
 /// # 参数
@@ -35,11 +100,410 @@ pub async fn read_encrypted_file(path: &str, app: &AppHandle) -> Result<String>
         .await
         .context("无法获取主加密密钥")?;
 
-    // 解密内容
-    decrypt_content(&ciphertext, &master_key)
+    // 解密内容（元数据由 read_file_meta 单独读取）
+    decrypt_note(&ciphertext, &master_key)
+        .map(|(content, _)| content)
         .with_context(|| format!("无法解密文件: {}", file_path.display()))
 }
 
+static PLAINTEXT_FOLDERS: std::sync::RwLock<Vec<String>> = std::sync::RwLock::new(Vec::new());
+
+// 修改明文目录设置的测试必须串行，否则会互相影响（设置是进程全局的）
+#[cfg(test)]
+pub(crate) static PLAINTEXT_FOLDERS_TEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// 设置以明文保存的目录（进程全局，随工作区配置生效；空列表表示全部加密）
+/// 
+/// 模式使用 Git pathspec 语法，相对工作区根目录，例如 `Public` 或 `shared/*`
+pub fn set_plaintext_folders(patterns: &[String]) {
+    *PLAINTEXT_FOLDERS.write().unwrap_or_else(|e| e.into_inner()) = normalize_plaintext_folders(patterns);
+}
+
+fn normalize_plaintext_folders(patterns: &[String]) -> Vec<String> {
+    patterns
+        .iter()
+        .map(|p| p.trim().trim_matches('/').to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// 相对工作区的路径（`/` 分隔，.enc 后缀可有可无）是否位于明文目录中
+pub fn is_plaintext_path(relative_path: &str) -> bool {
+    let patterns = PLAINTEXT_FOLDERS.read().unwrap_or_else(|e| e.into_inner());
+    matches_plaintext_folders(&patterns, relative_path)
+}
+
+/// 路径是否匹配给定的明文目录模式（用于非当前工作区，见 `vault_plaintext_folders`）
+fn matches_plaintext_folders(patterns: &[String], relative_path: &str) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+    let path = relative_path.trim_start_matches('/');
+    let path = path.strip_suffix(".enc").unwrap_or(path);
+    match git2::Pathspec::new(patterns.iter()) {
+        Ok(spec) => spec.matches_path(Path::new(path), git2::PathspecFlags::DEFAULT),
+        Err(e) => {
+            tracing::warn!("明文目录模式无效: {}", e);
+            false
+        }
+    }
+}
+
+/// 读取明文目录中的文件（不解密，路径不带 .enc 后缀）
+/// 
+/// 目录设为明文之前保存的笔记只有加密版本（`.enc`）时，解密读取该版本。
+pub async fn read_plaintext_file(path: &str, app: &AppHandle) -> Result<String> {
+    let file_path = Path::new(path.strip_suffix(".enc").unwrap_or(path));
+    match fs::read(file_path).await {
+        Ok(bytes) => String::from_utf8(bytes)
+            .with_context(|| format!("文件不是 UTF-8 文本: {}", file_path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => read_encrypted_file(path, app).await,
+        Err(e) => Err(e).with_context(|| format!("无法读取文件: {}", file_path.display())),
+    }
+}
+
+/// 不加密写入明文目录中的文件（路径不带 .enc 后缀）
+/// 
+/// 写入后删除同名的旧加密版本，避免同一笔记在仓库中出现两份。
+pub async fn write_plaintext_file(path: &str, content: &str) -> Result<()> {
+    let file_path = PathBuf::from(path.strip_suffix(".enc").unwrap_or(path));
+    let _lock = lock_path(&file_path).await;
+
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("无法创建目录: {}", parent.display()))?;
+    }
+    fs::write(&file_path, content)
+        .await
+        .with_context(|| format!("无法写入文件: {}", file_path.display()))?;
+
+    let encrypted = PathBuf::from(format!("{}.enc", file_path.display()));
+    match fs::remove_file(&encrypted).await {
+        Ok(()) => tracing::info!("write_plaintext_file: 已删除旧的加密版本: {}", encrypted.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("无法删除旧的加密版本: {}", encrypted.display())),
+    }
+    Ok(())
+}
+
+/// 把明文目录中遗留的加密版本转换为明文文件
+/// 
+/// 目录设为明文之前保存的笔记只有 `.enc` 版本：解密后写出原始文件，再删除加密版本；
+/// 已有原始文件时加密版本是过期副本，直接删除。无法解密的文件保留并记录警告。
+/// 
+/// # 返回
+/// 返回转换（或删除）的加密文件数
+pub async fn convert_plaintext_folder_notes(workspace: &Path, key: &[u8]) -> Result<usize> {
+    let mut converted = 0;
+    for encrypted in collect_encrypted_files(workspace) {
+        if !is_plaintext_path(&logical_relative_path(workspace, &encrypted)) {
+            continue;
+        }
+        let plain = PathBuf::from(encrypted.to_string_lossy().strip_suffix(".enc").unwrap_or_default());
+        let _lock = lock_paths(&[&encrypted, &plain]).await;
+        if !plain.exists() {
+            let ciphertext = fs::read(&encrypted)
+                .await
+                .with_context(|| format!("无法读取文件: {}", encrypted.display()))?;
+            let body = match decrypt_note_bytes(&ciphertext, key) {
+                Ok((body, _)) => body,
+                Err(e) => {
+                    tracing::warn!("[convert_plaintext_folder_notes] 无法解密 {}，保留加密版本: {}", encrypted.display(), e);
+                    continue;
+                }
+            };
+            fs::write(&plain, body)
+                .await
+                .with_context(|| format!("无法写入文件: {}", plain.display()))?;
+        }
+        fs::remove_file(&encrypted)
+            .await
+            .with_context(|| format!("无法删除旧的加密版本: {}", encrypted.display()))?;
+        converted += 1;
+    }
+    Ok(converted)
+}
+
+/// 笔记时间信息
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FileMeta {
+    pub created: String,
+    pub modified: String,
+    /// 为 true 时时间来自密文内的元数据；旧格式文件为 false，时间取自文件系统（可能已被 checkout 重置）
+    pub embedded: bool,
+}
+
+/// 读取笔记的创建/最后编辑时间
+/// 
+/// # 参数
+/// - `path`: 文件路径（会自动添加 .enc 扩展名）
+/// - `app`: Tauri 应用句柄，用于获取密钥
+/// 
+/// # 返回
+/// 返回笔记时间信息
+pub async fn read_file_meta(path: &str, app: &AppHandle) -> Result<FileMeta> {
+    let file_path = if path.ends_with(".enc") {
+        PathBuf::from(path)
+    } else {
+        PathBuf::from(format!("{}.enc", path))
+    };
+
+    let ciphertext = fs::read(&file_path)
+        .await
+        .with_context(|| format!("无法读取文件: {}", file_path.display()))?;
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let (_, meta) = decrypt_note(&ciphertext, &master_key)
+        .with_context(|| format!("无法解密文件: {}", file_path.display()))?;
+
+    if let Some(meta) = meta {
+        return Ok(FileMeta {
+            created: meta.created,
+            modified: meta.modified,
+            embedded: true,
+        });
+    }
+
+    // 旧格式文件：退回到文件系统时间
+    let metadata = fs::metadata(&file_path)
+        .await
+        .with_context(|| format!("无法读取文件信息: {}", file_path.display()))?;
+    let meta = filesystem_meta(&metadata);
+
+    Ok(FileMeta {
+        created: meta.created,
+        modified: meta.modified,
+        embedded: false,
+    })
+}
+
+/// 旧格式文件没有内嵌元数据时，用文件系统时间代替
+fn filesystem_meta(metadata: &std::fs::Metadata) -> NoteMeta {
+    let to_rfc3339 = |time: std::io::Result<std::time::SystemTime>| {
+        time.ok()
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+    };
+    let modified = to_rfc3339(metadata.modified())
+        .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+    let created = to_rfc3339(metadata.created()).unwrap_or_else(|| modified.clone());
+    NoteMeta { created, modified }
+}
+
+/// 用当前格式重新加密文件并原子替换（先写临时文件再重命名）
+async fn rekey_note(file_path: &Path, key: &[u8], compress: bool) -> Result<()> {
+    let _lock = lock_path(file_path).await;
+
+    let ciphertext = fs::read(file_path)
+        .await
+        .with_context(|| format!("无法读取文件: {}", file_path.display()))?;
+    let (content, meta) = decrypt_note(&ciphertext, key)
+        .with_context(|| format!("无法解密文件: {}", file_path.display()))?;
+    let meta = match meta {
+        Some(meta) => meta,
+        None => {
+            let metadata = fs::metadata(file_path)
+                .await
+                .with_context(|| format!("无法读取文件信息: {}", file_path.display()))?;
+            filesystem_meta(&metadata)
+        }
+    };
+    let reencrypted = encrypt_note(&content, &meta, compress, key).context("加密内容失败")?;
+
+    let file_name = file_path
+        .file_name()
+        .with_context(|| format!("无效的文件路径: {}", file_path.display()))?;
+    let tmp = file_path.with_file_name(format!(".{}.rekey.tmp", file_name.to_string_lossy()));
+    fs::write(&tmp, &reencrypted)
+        .await
+        .with_context(|| format!("无法写入临时文件: {}", tmp.display()))?;
+    if let Err(e) = fs::rename(&tmp, file_path).await {
+        let _ = fs::remove_file(&tmp).await;
+        return Err(e).with_context(|| format!("无法替换文件: {}", file_path.display()));
+    }
+    Ok(())
+}
+
+/// 用当前主密钥和当前加密格式重新加密单个文件（例如修复完整性检查发现的旧格式文件）
+/// 
+/// 内容和创建/编辑时间保持不变；旧格式文件没有内嵌时间时取文件系统时间
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `relative_path`: 相对工作区的文件路径（会自动添加 .enc 扩展名）
+/// - `app`: Tauri 应用句柄，用于获取密钥和压缩设置
+pub async fn rekey_file(workspace_path: &str, relative_path: &str, app: &AppHandle) -> Result<()> {
+    let relative = if relative_path.ends_with(".enc") {
+        relative_path.to_string()
+    } else {
+        format!("{}.enc", relative_path)
+    };
+    let file_path = confine_to_workspace(Path::new(workspace_path), Path::new(&relative))?;
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let compress = crate::commands::read_workspace_config_or_default(app, "rekey_file")
+        .await
+        .compress_notes;
+
+    rekey_note(&file_path, &master_key, compress).await?;
+    tracing::info!("[rekey_file] 已重新加密 {}", relative);
+    Ok(())
+}
+
+/// 计算明文内容的哈希（Git blob 的 SHA-1，十六进制）
+/// 
+/// 只取决于解密后的内容：同一内容的两次加密（nonce 不同、元数据不同）哈希相同
+pub fn content_hash(plaintext: &[u8]) -> Result<String> {
+    let oid = git2::Oid::hash_object(git2::ObjectType::Blob, plaintext)
+        .context("无法计算内容哈希")?;
+    Ok(oid.to_string())
+}
+
+/// 计算工作区内文件解密后内容的哈希（编辑器据此判断磁盘上的文件是否与打开的版本不同）
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `relative_path`: 工作区内的文件路径（.enc 后缀可省略）
+/// - `app`: Tauri 应用句柄，用于获取密钥
+/// 
+/// # 返回
+/// 返回十六进制哈希
+pub async fn file_content_hash(workspace_path: &str, relative_path: &str, app: &AppHandle) -> Result<String> {
+    let relative = if relative_path.ends_with(".enc") {
+        relative_path.to_string()
+    } else {
+        format!("{}.enc", relative_path)
+    };
+    let file_path = confine_to_workspace(Path::new(workspace_path), Path::new(&relative))?;
+
+    let ciphertext = fs::read(&file_path)
+        .await
+        .with_context(|| format!("无法读取文件: {}", file_path.display()))?;
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let (plaintext, _) = decrypt_note_bytes(&ciphertext, &master_key)
+        .with_context(|| format!("无法解密文件: {}", file_path.display()))?;
+    content_hash(&plaintext)
+}
+
+/// 列表预览默认返回的字符数
+pub const DEFAULT_PREVIEW_CHARS: usize = 200;
+
+/// 截取文本开头最多 `max_chars` 个字符（按 Unicode 字符计数，不会截断在多字节字符中间）
+pub fn preview_text(content: &str, max_chars: usize) -> &str {
+    match content.char_indices().nth(max_chars) {
+        Some((end, _)) => &content[..end],
+        None => content,
+    }
+}
+
+/// 读取笔记开头的一段内容，用于侧边栏等列表中的预览
+/// 
+/// 笔记整体使用 AES-GCM 加密，必须解密整个文件才能校验；这里只返回开头部分，
+/// 避免把整篇笔记通过 IPC 传给前端。
+/// 
+/// # 参数
+/// - `relative_path`: 相对于工作区的路径（.enc 后缀可省略）
+/// - `max_chars`: 最多返回的字符数
+pub async fn read_file_preview(
+    workspace_path: &str,
+    relative_path: &str,
+    max_chars: usize,
+    app: &AppHandle,
+) -> Result<String> {
+    let relative = if relative_path.ends_with(".enc") {
+        relative_path.to_string()
+    } else {
+        format!("{}.enc", relative_path)
+    };
+    let file_path = confine_to_workspace(Path::new(workspace_path), Path::new(&relative))?;
+
+    let ciphertext = fs::read(&file_path)
+        .await
+        .with_context(|| format!("无法读取文件: {}", file_path.display()))?;
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let (content, _) = decrypt_note(&ciphertext, &master_key)
+        .with_context(|| format!("无法解密文件: {}", file_path.display()))?;
+    Ok(preview_text(&content, max_chars).to_string())
+}
+
+/// 批量读取中单个文件的结果：成功时 `content` 有值，失败时 `error` 说明原因
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BatchReadResult {
+    pub content: Option<String>,
+    pub error: Option<String>,
+}
+
+impl BatchReadResult {
+    fn failed(error: impl Into<String>) -> Self {
+        BatchReadResult { content: None, error: Some(error.into()) }
+    }
+}
+
+/// 一次读取并解密多个笔记（多窗格视图、预览列表只需一次 IPC 调用）
+/// 
+/// 以 `decrypt_concurrency()` 为上限并发解密；单个文件失败不影响其它文件。
+/// 
+/// # 参数
+/// - `relative_paths`: 相对于工作区的路径（.enc 后缀可省略）
+/// 
+/// # 返回
+/// 以请求中的路径为键的读取结果
+pub async fn read_files_batch(
+    workspace_path: &str,
+    relative_paths: &[String],
+    app: &AppHandle,
+) -> Result<std::collections::BTreeMap<String, BatchReadResult>> {
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    Ok(read_files_with_key(Path::new(workspace_path), relative_paths, &master_key).await)
+}
+
+async fn read_files_with_key(
+    workspace: &Path,
+    relative_paths: &[String],
+    key: &[u8],
+) -> std::collections::BTreeMap<String, BatchReadResult> {
+    let mut results = std::collections::BTreeMap::new();
+    let mut requested: std::collections::HashMap<PathBuf, Vec<String>> = std::collections::HashMap::new();
+    for relative_path in relative_paths {
+        let relative = if relative_path.ends_with(".enc") {
+            relative_path.clone()
+        } else {
+            format!("{}.enc", relative_path)
+        };
+        match confine_to_workspace(workspace, Path::new(&relative)) {
+            Ok(file_path) => requested.entry(file_path).or_default().push(relative_path.clone()),
+            Err(e) => {
+                results.insert(relative_path.clone(), BatchReadResult::failed(e.to_string()));
+            }
+        }
+    }
+
+    let files = requested.keys().cloned().collect();
+    let decrypted = decrypt_all_with_limit(files, decrypt_concurrency(), key, |file| {
+        String::from_utf8(file.bytes).map_err(|_| "文件内容不是有效的 UTF-8 文本".to_string())
+    })
+    .await;
+    for (file_path, result) in decrypted {
+        let result = match result {
+            Some(Ok(content)) => BatchReadResult { content: Some(content), error: None },
+            Some(Err(e)) => BatchReadResult::failed(e),
+            None => BatchReadResult::failed(format!("无法读取或解密文件: {}", file_path.display())),
+        };
+        for relative_path in requested.remove(&file_path).unwrap_or_default() {
+            results.insert(relative_path, result.clone());
+        }
+    }
+    results
+}
+
 /// 加密并写入文件内容
 /// 
 /// # 参数
@@ -66,8 +530,28 @@ pub async fn write_encrypted_file(
         .await
         .context("无法获取主加密密钥")?;
 
-    // 加密内容
-    let ciphertext = encrypt_content(content, &master_key)
+    // 同一文件的并发写入必须串行，否则交错写入会破坏密文帧
+    let _lock = lock_path(&file_path).await;
+
+    // 保留已有笔记的创建时间；旧格式或无法解密的文件视为新建
+    let now = chrono::Utc::now().to_rfc3339();
+    let created = match fs::read(&file_path).await {
+        Ok(existing) => decrypt_note(&existing, &master_key)
+            .ok()
+            .and_then(|(_, meta)| meta)
+            .map(|meta| meta.created),
+        Err(_) => None,
+    };
+    let meta = NoteMeta {
+        created: created.unwrap_or_else(|| now.clone()),
+        modified: now,
+    };
+
+    // 加密内容（按工作区配置决定是否先压缩）
+    let compress = crate::commands::read_workspace_config_or_default(app, "write_encrypted_file")
+        .await
+        .compress_notes;
+    let ciphertext = encrypt_note(content, &meta, compress, &master_key)
         .context("加密内容失败")?;
 
     // 确保目录存在
@@ -85,6 +569,127 @@ pub async fn write_encrypted_file(
     Ok(())
 }
 
+/// 加密并写入一组文件：全部写入成功，或者恢复到写入之前的状态
+/// 
+/// 所有文件先在内存中加密，再逐个写入；任何一个写入失败时，已写入的文件恢复为原内容（新建的文件被删除）。
+/// 明文目录中的文件（见 `is_plaintext_path`）不加密，写入原始文件，全部写入成功后删除同名的旧加密版本。
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `files`: (相对路径, 明文内容)；路径的 .enc 后缀可省略
+/// - `app`: Tauri 应用句柄，用于获取密钥和工作区配置
+/// 
+/// # 返回
+/// 返回写入的文件相对于工作区的路径（加密文件带 .enc 后缀），以及被删除的旧加密版本的路径
+pub async fn write_files(workspace_path: &str, files: &[(String, String)], app: &AppHandle) -> Result<Vec<String>> {
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let compress = crate::commands::read_workspace_config_or_default(app, "write_files")
+        .await
+        .compress_notes;
+    write_files_with_key(Path::new(workspace_path), files, &master_key, compress).await
+}
+
+/// 内容可以是任意字节（`String` 或 `Vec<u8>`），非 UTF-8 内容按原始字节加密
+pub(crate) async fn write_files_with_key<C: AsRef<[u8]>>(
+    workspace: &Path,
+    files: &[(String, C)],
+    key: &[u8],
+    compress: bool,
+) -> Result<Vec<String>> {
+    let root = workspace
+        .canonicalize()
+        .with_context(|| format!("无法解析工作区路径: {}", workspace.display()))?;
+    let mut targets = Vec::with_capacity(files.len());
+    for (relative_path, content) in files {
+        let relative = if is_plaintext_path(relative_path) {
+            relative_path.strip_suffix(".enc").unwrap_or(relative_path).to_string()
+        } else if relative_path.ends_with(".enc") {
+            relative_path.clone()
+        } else {
+            format!("{}.enc", relative_path)
+        };
+        let file_path = confine_to_workspace(workspace, Path::new(&relative))?;
+        if targets.iter().any(|(existing, _): &(PathBuf, &C)| *existing == file_path) {
+            anyhow::bail!("重复的文件路径: {}", relative_path);
+        }
+        targets.push((file_path, content));
+    }
+
+    let paths: Vec<&Path> = targets.iter().map(|(path, _)| path.as_path()).collect();
+    let _lock = lock_paths(&paths).await;
+
+    // 先加密全部内容并保存原文件，写入过程中不再有可能失败的解密/加密步骤
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut prepared = Vec::with_capacity(targets.len());
+    for (file_path, content) in &targets {
+        let original = fs::read(file_path).await.ok();
+        if !file_path.to_string_lossy().ends_with(".enc") {
+            prepared.push((file_path, content.as_ref().to_vec(), original));
+            continue;
+        }
+        let created = original
+            .as_ref()
+            .and_then(|existing| decrypt_note(existing, key).ok())
+            .and_then(|(_, meta)| meta)
+            .map(|meta| meta.created);
+        let meta = NoteMeta {
+            created: created.unwrap_or_else(|| now.clone()),
+            modified: now.clone(),
+        };
+        let ciphertext = encrypt_note_bytes(content.as_ref(), &meta, compress, key)
+            .with_context(|| format!("加密内容失败: {}", file_path.display()))?;
+        prepared.push((file_path, ciphertext, original));
+    }
+
+    let mut written: Vec<(&PathBuf, &Option<Vec<u8>>)> = Vec::new();
+    for (file_path, ciphertext, original) in &prepared {
+        let result = async {
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)
+                    .await
+                    .with_context(|| format!("无法创建目录: {}", parent.display()))?;
+            }
+            fs::write(file_path, ciphertext)
+                .await
+                .with_context(|| format!("无法写入文件: {}", file_path.display()))
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("[write_files] 写入失败，恢复已写入的 {} 个文件: {}", written.len(), e);
+            for (path, original) in written.iter().rev() {
+                let restored = match original {
+                    Some(bytes) => fs::write(path, bytes).await,
+                    None => fs::remove_file(path).await,
+                };
+                if let Err(e) = restored {
+                    tracing::warn!("[write_files] 无法恢复 {}: {}", path.display(), e);
+                }
+            }
+            return Err(e);
+        }
+        written.push((file_path, original));
+    }
+
+    let mut paths: Vec<String> = targets
+        .iter()
+        .map(|(file_path, _)| workspace_relative_path(&root, file_path))
+        .collect();
+    for (file_path, _) in &targets {
+        if file_path.to_string_lossy().ends_with(".enc") {
+            continue;
+        }
+        let encrypted = PathBuf::from(format!("{}.enc", file_path.display()));
+        match fs::remove_file(&encrypted).await {
+            Ok(()) => paths.push(workspace_relative_path(&root, &encrypted)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("[write_files] 无法删除旧的加密版本 {}: {}", encrypted.display(), e),
+        }
+    }
+    Ok(paths)
+}
+
 /// 列出目录中的文件和文件夹
 /// 
 /// # 参数
@@ -104,6 +709,7 @@ pub async fn list_directory(path: &str) -> Result<Vec<FileInfo>> {
     }
 
     let mut entries = Vec::new();
+    let mut disk_names = Vec::new();
     let mut dir = fs::read_dir(dir_path)
         .await
         .with_context(|| format!("无法读取目录: {}", path))?;
@@ -125,6 +731,10 @@ pub async fn list_directory(path: &str) -> Result<Vec<FileInfo>> {
         let is_dir = metadata.is_dir();
         let is_file = metadata.is_file();
 
+        // 显示名统一为 NFC（macOS 返回 NFD）；path 保留磁盘上的原始形式，保证可以打开
+        disk_names.push(name.clone());
+        let name = crate::git::to_nfc(&name);
+
         entries.push(FileInfo {
             name,
             path: path.to_string_lossy().to_string(),
@@ -133,6 +743,16 @@ pub async fn list_directory(path: &str) -> Result<Vec<FileInfo>> {
         });
     }
 
+    // 仅大小写或 Unicode 规范化形式不同的条目在 Windows/macOS 上会落到同一个文件
+    let collisions = crate::git::find_case_collisions(&disk_names);
+    if !collisions.is_empty() {
+        tracing::warn!(
+            "list_directory: 警告：{} 中存在仅大小写或 Unicode 规范化形式不同的条目，在 Windows/macOS 上会冲突: {:?}",
+            dir_path.display(),
+            collisions
+        );
+    }
+
     // 排序：目录在前，然后按名称排序
     entries.sort_by(|a, b| {
         match (a.is_directory, b.is_directory) {
@@ -150,6 +770,7 @@ pub async fn list_directory(path: &str) -> Result<Vec<FileInfo>> {
 /// # 参数
 /// - `path`: 文件路径（会自动添加 .enc 扩展名）
 /// - `content`: 初始内容（可选）
+/// - `plaintext`: 文件位于明文目录中（见 `is_plaintext_path`）时为 true，不加密、不添加 .enc 扩展名
 /// - `app`: Tauri 应用句柄，用于加密
 /// 
 /// # 返回
@@ -157,17 +778,19 @@ pub async fn list_directory(path: &str) -> Result<Vec<FileInfo>> {
 pub async fn create_file(
     path: &str,
     content: &str,
+    plaintext: bool,
     app: &AppHandle,
 ) -> Result<()> {
-    // 确保路径以 .enc 结尾
-    let file_path = if path.ends_with(".enc") {
-        PathBuf::from(path)
+    let logical = path.strip_suffix(".enc").unwrap_or(path);
+    let encrypted_path = PathBuf::from(format!("{}.enc", logical));
+    let file_path = if plaintext {
+        PathBuf::from(logical)
     } else {
-        PathBuf::from(format!("{}.enc", path))
+        encrypted_path.clone()
     };
 
-    // 如果文件已存在，返回错误
-    if file_path.exists() {
+    // 如果文件已存在（明文目录中也包括旧的加密版本），返回错误
+    if file_path.exists() || encrypted_path.exists() {
         anyhow::bail!("文件已存在: {}", file_path.display());
     }
 
@@ -178,29 +801,138 @@ pub async fn create_file(
             .with_context(|| format!("无法创建目录: {}", parent.display()))?;
     }
 
+    if plaintext {
+        return write_plaintext_file(logical, content).await;
+    }
     // 加密并写入内容
     write_encrypted_file(path, content, app).await
 }
 
-/// 创建新目录
+/// 模板目录（相对工作区根目录），模板与普通笔记一样加密存储并参与同步
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// 替换模板中的 `{{变量}}` 占位符
 /// 
-/// # 参数
-/// - `path`: 目录路径
+/// 内置变量：`{{date}}`（今天，`YYYY-MM-DD`）、`{{time}}`（当前时间，`HH:MM`）、`{{title}}`（新文件名）。
+/// 调用方传入的同名变量优先；未知的占位符原样保留。
 /// 
-/// # 返回
-/// 成功时返回 Ok(())
-pub async fn create_directory(path: &str) -> Result<()> {
-    let dir_path = Path::new(path);
-    
-    if dir_path.exists() {
-        anyhow::bail!("目录已存在: {}", path);
-    }
+/// # 参数
+/// - `template`: 模板内容
+/// - `title`: 新文件的标题
+/// - `variables`: 额外的变量
+pub fn render_template(
+    template: &str,
+    title: &str,
+    variables: &std::collections::HashMap<String, String>,
+) -> String {
+    let now = chrono::Local::now();
+    let builtin = |name: &str| -> Option<String> {
+        match name {
+            "date" => Some(now.format("%Y-%m-%d").to_string()),
+            "time" => Some(now.format("%H:%M").to_string()),
+            "title" => Some(title.to_string()),
+            _ => None,
+        }
+    };
 
-    fs::create_dir_all(dir_path)
-        .await
-        .with_context(|| format!("无法创建目录: {}", path))?;
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        output.push_str(&rest[..start]);
+        match variables.get(name).cloned().or_else(|| builtin(name)) {
+            Some(value) => output.push_str(&value),
+            None => output.push_str(&rest[start..start + len + 4]),
+        }
+        rest = &rest[start + len + 4..];
+    }
+    output.push_str(rest);
+    output
+}
 
-    Ok(())
+/// 从笔记路径得到标题（文件名去掉 .enc 和扩展名）
+fn note_title(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let name = name.strip_suffix(".enc").unwrap_or(&name);
+    Path::new(name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// 用模板创建新文件
+/// 
+/// 模板位于工作区的 `templates/` 目录，例如 `templates/daily.md.enc`，`template_name` 传 `daily` 或 `daily.md` 均可
+/// 
+/// # 参数
+/// - `workspace`: 工作区根目录
+/// - `dest_path`: 新文件路径（会自动添加 .enc 扩展名）
+/// - `template_name`: 模板名称
+/// - `variables`: 额外的模板变量
+/// - `app`: Tauri 应用句柄，用于加解密
+pub async fn create_file_from_template(
+    workspace: &Path,
+    dest_path: &str,
+    template_name: &str,
+    variables: &std::collections::HashMap<String, String>,
+    app: &AppHandle,
+) -> Result<()> {
+    if template_name.is_empty()
+        || template_name.contains(['/', '\\'])
+        || template_name.starts_with('.')
+    {
+        anyhow::bail!("无效的模板名称: {}", template_name);
+    }
+    let templates = workspace.join(TEMPLATES_DIR);
+    let name = template_name.strip_suffix(".enc").unwrap_or(template_name);
+    // 模板目录本身可以设为明文目录，此时模板是原始文件
+    let template_path = [name.to_string(), format!("{}.md", name)]
+        .into_iter()
+        .flat_map(|file| {
+            let plaintext = is_plaintext_path(&format!("{}/{}", TEMPLATES_DIR, file));
+            let raw = templates.join(&file);
+            plaintext
+                .then_some(raw)
+                .into_iter()
+                .chain(std::iter::once(templates.join(format!("{}.enc", file))))
+        })
+        .find(|path| path.is_file())
+        .with_context(|| format!("模板不存在: {}", template_name))?;
+
+    let template = if template_path.to_string_lossy().ends_with(".enc") {
+        read_encrypted_file(&template_path.to_string_lossy(), app).await?
+    } else {
+        read_plaintext_file(&template_path.to_string_lossy(), app).await?
+    };
+    let content = render_template(&template, &note_title(Path::new(dest_path)), variables);
+    let plaintext = is_plaintext_path(&workspace_relative_path(workspace, Path::new(dest_path)));
+    create_file(dest_path, &content, plaintext, app).await?;
+    tracing::info!("[create_file_from_template] 已用模板 {} 创建 {}", template_name, dest_path);
+    Ok(())
+}
+
+/// 创建新目录
+/// 
+/// # 参数
+/// - `path`: 目录路径
+/// 
+/// # 返回
+/// 成功时返回 Ok(())
+pub async fn create_directory(path: &str) -> Result<()> {
+    let dir_path = Path::new(path);
+    
+    if dir_path.exists() {
+        anyhow::bail!("目录已存在: {}", path);
+    }
+
+    fs::create_dir_all(dir_path)
+        .await
+        .with_context(|| format!("无法创建目录: {}", path))?;
+
+    Ok(())
 }
 
 /// 删除文件
@@ -212,6 +944,7 @@ pub async fn create_directory(path: &str) -> Result<()> {
 /// 成功时返回 Ok(())
 pub async fn delete_file(path: &str) -> Result<()> {
     let file_path = Path::new(path);
+    let _lock = lock_path(file_path).await;
     
     if !file_path.exists() {
         anyhow::bail!("文件不存在: {}", path);
@@ -224,6 +957,60 @@ pub async fn delete_file(path: &str) -> Result<()> {
     Ok(())
 }
 
+/// 删除文件，并向上删除因此变空的父目录（直到遇到非空目录或工作区根目录）
+/// 
+/// Git 不跟踪空目录，残留的空目录在其他设备上不会出现，删除它们可以让各端目录树保持一致
+/// 
+/// # 参数
+/// - `path`: 文件路径
+/// - `workspace`: 工作区根目录（不会被删除）
+/// 
+/// # 返回
+/// 返回删除的空目录数
+pub async fn delete_file_and_prune(path: &str, workspace: &Path) -> Result<usize> {
+    delete_file(path).await?;
+
+    let root = workspace
+        .canonicalize()
+        .with_context(|| format!("无法解析工作区路径: {}", workspace.display()))?;
+    let mut pruned = 0;
+    let mut current = Path::new(path).parent().and_then(|dir| dir.canonicalize().ok());
+    while let Some(dir) = current {
+        if dir == root || !dir.starts_with(&root) {
+            break;
+        }
+        // remove_dir 只删除空目录，目录非空时失败即停止
+        if fs::remove_dir(&dir).await.is_err() {
+            break;
+        }
+        pruned += 1;
+        current = dir.parent().map(Path::to_path_buf);
+    }
+    if pruned > 0 {
+        tracing::info!("[delete_file_and_prune] 已删除 {} 个空目录", pruned);
+    }
+    Ok(pruned)
+}
+
+/// 检查目录是否允许整体删除
+/// 
+/// 拒绝 `.git` 目录及其内部路径，以及直接包含 `.git` 的目录（即仓库/工作区根目录），
+/// 防止前端传错路径时 `remove_dir_all` 抹掉整个仓库。
+fn ensure_not_protected_directory(dir_path: &Path) -> Result<()> {
+    let canonical = dir_path
+        .canonicalize()
+        .unwrap_or_else(|_| dir_path.to_path_buf());
+
+    if canonical.components().any(|c| c.as_os_str() == ".git") {
+        anyhow::bail!("拒绝删除 Git 元数据目录: {}", dir_path.display());
+    }
+    if canonical.join(".git").exists() {
+        anyhow::bail!("拒绝删除仓库根目录: {}", dir_path.display());
+    }
+
+    Ok(())
+}
+
 /// 删除目录
 /// 
 /// # 参数
@@ -233,11 +1020,14 @@ pub async fn delete_file(path: &str) -> Result<()> {
 /// 成功时返回 Ok(())
 pub async fn delete_directory(path: &str) -> Result<()> {
     let dir_path = Path::new(path);
+    let _lock = lock_path(dir_path).await;
     
     if !dir_path.exists() {
         anyhow::bail!("目录不存在: {}", path);
     }
 
+    ensure_not_protected_directory(dir_path)?;
+
     fs::remove_dir_all(dir_path)
         .await
         .with_context(|| format!("无法删除目录: {}", path))?;
@@ -256,6 +1046,12 @@ pub async fn delete_directory(path: &str) -> Result<()> {
 pub async fn rename_file_or_directory(old_path: &str, new_path: &str) -> Result<()> {
     let old = Path::new(old_path);
     let new = Path::new(new_path);
+    // 移动目录会改变其中所有文件的路径，只锁目录本身挡不住对子文件的并发写入，需要独占工作区
+    let (_workspace_lock, _lock) = if old.is_dir() {
+        (Some(lock_workspace().await), None)
+    } else {
+        (None, Some(lock_paths(&[old, new]).await))
+    };
     
     if !old.exists() {
         anyhow::bail!("文件或目录不存在: {}", old_path);
@@ -279,6 +1075,159 @@ pub async fn rename_file_or_directory(old_path: &str, new_path: &str) -> Result<
     Ok(())
 }
 
+/// 用另一把密钥重新加密笔记并写到目标路径，确认能用新密钥解密后再删除源文件
+/// 
+/// 笔记的创建/编辑时间原样保留；目标（或其明文/加密版本）已存在时报错。
+/// 不带 .enc 后缀的路径是明文目录中的原始文件：源文件直接读取，目标文件不加密写入
+async fn move_note_reencrypted(source: &Path, source_key: &[u8], dest: &Path, dest_key: &[u8]) -> Result<()> {
+    let dest_twin = match dest.to_string_lossy().strip_suffix(".enc") {
+        Some(plain) => PathBuf::from(plain),
+        None => PathBuf::from(format!("{}.enc", dest.display())),
+    };
+    let _lock = lock_paths(&[source, dest, &dest_twin]).await;
+    if dest.exists() || dest_twin.exists() {
+        anyhow::bail!("目标路径已存在: {}", dest.display());
+    }
+
+    let bytes = fs::read(source)
+        .await
+        .with_context(|| format!("无法读取文件: {}", source.display()))?;
+    let (body, meta) = if source.to_string_lossy().ends_with(".enc") {
+        decrypt_note_bytes(&bytes, source_key)
+            .with_context(|| format!("无法解密文件: {}", source.display()))?
+    } else {
+        (bytes, None)
+    };
+    let reencrypted = if dest.to_string_lossy().ends_with(".enc") {
+        let meta = meta.unwrap_or_else(|| {
+            let now = chrono::Utc::now().to_rfc3339();
+            NoteMeta { created: now.clone(), modified: now }
+        });
+        // 目标工作区的配置可能用它自己的主密钥加密，这里不读取其压缩设置，统一不压缩
+        let reencrypted = encrypt_note_bytes(&body, &meta, false, dest_key).context("加密内容失败")?;
+        decrypt_note_bytes(&reencrypted, dest_key).context("重新加密后的内容无法用目标密钥解密")?;
+        reencrypted
+    } else {
+        body
+    };
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("无法创建目录: {}", parent.display()))?;
+    }
+    fs::write(dest, &reencrypted)
+        .await
+        .with_context(|| format!("无法写入文件: {}", dest.display()))?;
+    fs::remove_file(source)
+        .await
+        .with_context(|| format!("无法删除源文件: {}", source.display()))?;
+    Ok(())
+}
+
+/// 将笔记移动到另一个工作区（用源工作区的密钥解密，再用目标工作区的密钥加密）
+/// 
+/// 两个工作区都必须已有本机保存的密钥；目标工作区已有笔记时，先确认目标密钥能解密这些笔记，
+/// 避免用错误的密钥加密后删除源文件。两个工作区各自的明文目录设置都会生效：
+/// 明文目录中的源笔记直接读取原始文件，目标位于明文目录时不加密写入。
+/// 
+/// # 参数
+/// - `app`: Tauri 应用句柄，用于获取两个工作区的主密钥
+/// - `source`: 源工作区
+/// - `source_path`: 源文件路径（相对源工作区或其中的绝对路径，.enc 后缀可省略）
+/// - `dest`: 目标工作区
+/// - `dest_path`: 目标文件路径（相对目标工作区或其中的绝对路径，.enc 后缀可省略）
+/// 
+/// # 返回
+/// 返回被移走的源文件路径
+pub async fn move_between_vaults(
+    app: &AppHandle,
+    source: &crate::vaults::VaultEntry,
+    source_path: &str,
+    dest: &crate::vaults::VaultEntry,
+    dest_path: &str,
+) -> Result<PathBuf> {
+    if source.id == dest.id {
+        anyhow::bail!("源工作区和目标工作区相同，请使用移动命令");
+    }
+    let source_key = crate::keychain::load_vault_key(app, &source.id)
+        .with_context(|| format!("无法获取工作区 {} 的主密钥", source.name))?;
+    let dest_key = crate::keychain::load_vault_key(app, &dest.id)
+        .with_context(|| format!("无法获取工作区 {} 的主密钥", dest.name))?;
+
+    let source_root = Path::new(&source.path);
+    let (source_plain, source_encrypted, source_in_plaintext) =
+        vault_note_paths(source_root, source_path, &vault_plaintext_folders(source_root, &source_key))?;
+    // 明文目录中设为明文之前保存的笔记可能只有加密版本
+    let source_file = if source_in_plaintext && source_plain.is_file() {
+        source_plain
+    } else {
+        source_encrypted
+    };
+    if !source_file.is_file() {
+        anyhow::bail!("源文件不存在: {}", source_file.display());
+    }
+    let dest_root = Path::new(&dest.path);
+    let (dest_plain, dest_encrypted, dest_in_plaintext) =
+        vault_note_paths(dest_root, dest_path, &vault_plaintext_folders(dest_root, &dest_key))?;
+    let dest_file = if dest_in_plaintext { dest_plain } else { dest_encrypted };
+
+    if !can_decrypt_vault(dest_root, &dest_key) {
+        anyhow::bail!("工作区 {} 的主密钥无法解密其中已有的笔记，已取消移动", dest.name);
+    }
+    move_note_reencrypted(&source_file, &source_key, &dest_file, &dest_key).await?;
+
+    tracing::info!(
+        "[move_between_vaults] {} ({}) -> {} ({})",
+        source_file.display(),
+        source.name,
+        dest_file.display(),
+        dest.name
+    );
+    Ok(source_file)
+}
+
+/// 读取某个工作区配置中的明文目录（该工作区不一定是当前工作区，配置可能用它自己的主密钥加密）
+/// 
+/// 没有配置文件时返回空列表；配置无法读取时记录警告并按全部加密处理
+fn vault_plaintext_folders(vault_root: &Path, key: &[u8]) -> Vec<String> {
+    let config_file = vault_root.join(".config/settings.json");
+    let bytes = match std::fs::read(&config_file) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            tracing::warn!("[vault_plaintext_folders] 无法读取 {}: {}", config_file.display(), e);
+            return Vec::new();
+        }
+    };
+    let config = serde_json::from_slice::<crate::commands::WorkspaceConfig>(&bytes).or_else(|_| {
+        let content = crate::crypto::decrypt_content(&bytes, key)?;
+        Ok::<_, anyhow::Error>(serde_json::from_str::<crate::commands::WorkspaceConfig>(&content)?)
+    });
+    match config {
+        Ok(config) => normalize_plaintext_folders(&config.plaintext_folders),
+        Err(e) => {
+            tracing::warn!("[vault_plaintext_folders] 无法解析 {}: {}", config_file.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+/// 解析工作区中一篇笔记的路径
+/// 
+/// # 返回
+/// (原始文件路径, 加密文件路径, 是否位于明文目录中)；路径在工作区之外时返回错误
+fn vault_note_paths(vault_root: &Path, path: &str, plaintext_folders: &[String]) -> Result<(PathBuf, PathBuf, bool)> {
+    let logical = path.strip_suffix(".enc").unwrap_or(path);
+    let plain = confine_to_workspace(vault_root, Path::new(logical))?;
+    let encrypted = PathBuf::from(format!("{}.enc", plain.display()));
+    let root = vault_root
+        .canonicalize()
+        .with_context(|| format!("无法解析工作区路径: {}", vault_root.display()))?;
+    let in_plaintext = matches_plaintext_folders(plaintext_folders, &workspace_relative_path(&root, &plain));
+    Ok((plain, encrypted, in_plaintext))
+}
+
 /// 复制文件或目录
 /// 
 /// # 参数
@@ -298,6 +1247,7 @@ pub async fn copy_file_or_directory(source_path: &str, dest_path: &str) -> Resul
     if dest.exists() {
         anyhow::bail!("目标路径已存在: {}", dest_path);
     }
+    ensure_not_into_itself(source, dest)?;
 
     // 确保目标路径的父目录存在
     if let Some(parent) = dest.parent() {
@@ -307,8 +1257,8 @@ pub async fn copy_file_or_directory(source_path: &str, dest_path: &str) -> Resul
     }
 
     if source.is_dir() {
-        // 复制目录（递归）
-        copy_dir_all(source, dest)
+        // 复制目录（递归，受层级和条目数上限约束）
+        copy_dir_limited(source, dest, copy_limits())
             .await
             .with_context(|| format!("无法复制目录: {} -> {}", source_path, dest_path))?;
     } else {
@@ -321,9 +1271,64 @@ pub async fn copy_file_or_directory(source_path: &str, dest_path: &str) -> Resul
     Ok(())
 }
 
+/// 复制目录时默认允许的最大层级
+pub const DEFAULT_COPY_MAX_DEPTH: usize = 64;
+/// 复制目录时默认允许的最大条目数（文件和目录合计）
+pub const DEFAULT_COPY_MAX_ENTRIES: usize = 100_000;
+
+static COPY_MAX_DEPTH: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(DEFAULT_COPY_MAX_DEPTH);
+static COPY_MAX_ENTRIES: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(DEFAULT_COPY_MAX_ENTRIES);
+
+/// 复制目录的上限：超过时中止复制，避免异常深或异常大的目录树耗尽栈空间或磁盘
+#[derive(Debug, Clone, Copy)]
+pub struct CopyLimits {
+    pub max_depth: usize,
+    pub max_entries: usize,
+}
+
+/// 设置复制目录的上限（进程全局，随工作区配置生效；最小为 1）
+pub fn set_copy_limits(max_depth: usize, max_entries: usize) {
+    COPY_MAX_DEPTH.store(max_depth.max(1), std::sync::atomic::Ordering::Relaxed);
+    COPY_MAX_ENTRIES.store(max_entries.max(1), std::sync::atomic::Ordering::Relaxed);
+}
+
+/// 当前的复制目录上限
+pub fn copy_limits() -> CopyLimits {
+    CopyLimits {
+        max_depth: COPY_MAX_DEPTH.load(std::sync::atomic::Ordering::Relaxed),
+        max_entries: COPY_MAX_ENTRIES.load(std::sync::atomic::Ordering::Relaxed),
+    }
+}
+
+/// 在上限内递归复制目录；超过上限或复制失败时删除已复制的部分
+async fn copy_dir_limited(source: &Path, dest: &Path, limits: CopyLimits) -> Result<()> {
+    let mut copied = 0;
+    let result = copy_dir_all(source, dest, limits, 0, &mut copied).await;
+    if result.is_err() {
+        if let Err(e) = fs::remove_dir_all(dest).await {
+            tracing::warn!("无法清理复制了一半的目录 {}: {}", dest.display(), e);
+        }
+    }
+    result
+}
+
 /// 递归复制目录
-fn copy_dir_all<'a>(source: &'a Path, dest: &'a Path) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
+/// 
+/// `depth` 为当前目录相对复制起点的层级，`copied` 累计已复制的条目数
+fn copy_dir_all<'a>(
+    source: &'a Path,
+    dest: &'a Path,
+    limits: CopyLimits,
+    depth: usize,
+    copied: &'a mut usize,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
     Box::pin(async move {
+        if depth > limits.max_depth {
+            anyhow::bail!("目录层级超过 {} 层，已停止复制: {}", limits.max_depth, source.display());
+        }
+
         fs::create_dir_all(dest)
             .await
             .with_context(|| format!("无法创建目标目录: {}", dest.display()))?;
@@ -333,12 +1338,17 @@ fn copy_dir_all<'a>(source: &'a Path, dest: &'a Path) -> std::pin::Pin<Box<dyn s
             .with_context(|| format!("无法读取源目录: {}", source.display()))?;
 
         while let Some(entry) = entries.next_entry().await? {
+            *copied += 1;
+            if *copied > limits.max_entries {
+                anyhow::bail!("目录中的条目超过 {} 个，已停止复制", limits.max_entries);
+            }
+
             let entry_path = entry.path();
             let dest_path = dest.join(entry.file_name());
 
             let metadata = entry.metadata().await?;
             if metadata.is_dir() {
-                copy_dir_all(&entry_path, &dest_path).await?;
+                copy_dir_all(&entry_path, &dest_path, limits, depth + 1, copied).await?;
             } else {
                 let _bytes_copied = fs::copy(&entry_path, &dest_path)
                     .await
@@ -362,6 +1372,7 @@ pub async fn move_file_or_directory(source_path: &str, dest_path: &str) -> Resul
     // 移动操作实际上就是重命名，但需要确保目标路径的父目录存在
     let source = Path::new(source_path);
     let dest = Path::new(dest_path);
+    let _lock = lock_paths(&[source, dest]).await;
     
     if !source.exists() {
         anyhow::bail!("源文件或目录不存在: {}", source_path);
@@ -376,16 +1387,8 @@ pub async fn move_file_or_directory(source_path: &str, dest_path: &str) -> Resul
     let source_canonical = source.canonicalize()
         .with_context(|| format!("无法解析源路径: {}", source_path))?;
 
-    // 防止将目录移动到其自身或子目录中
-    // 检查目标路径的父目录是否是源路径的子目录
-    if let Some(dest_parent) = dest.parent() {
-        if let Ok(dest_parent_canonical) = dest_parent.canonicalize() {
-            // 如果目标路径的父目录是源路径的子目录，则不允许
-            if dest_parent_canonical.starts_with(&source_canonical) && dest_parent_canonical != source_canonical {
-                anyhow::bail!("不能将目录移动到其自身或子目录中");
-            }
-        }
-    }
+    // 防止将目录移动到其自身或子目录中（目标的父目录尚不存在时同样检查）
+    ensure_not_into_itself(&source_canonical, dest)?;
 
     // 确保目标路径的父目录存在
     if let Some(parent) = dest.parent() {
@@ -402,130 +1405,1092 @@ pub async fn move_file_or_directory(source_path: &str, dest_path: &str) -> Resul
     Ok(())
 }
 
-/// 文件信息结构
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct FileInfo {
-    pub name: String,
-    pub path: String,
-    pub is_directory: bool,
-    pub is_file: bool,
+/// 查找目录生效的氛围配置文件（.vnode.json）
+/// 
+/// 从 `dir` 开始逐级向上查找，直到工作区根目录为止，返回最近的一个配置文件；
+/// `dir` 不在工作区内时只检查 `dir` 本身。
+/// 
+/// # 参数
+/// - `workspace`: 工作区根目录
+/// - `dir`: 需要读取氛围配置的目录
+pub fn find_atmosphere_config(workspace: &Path, dir: &Path) -> Option<PathBuf> {
+    let root = workspace.canonicalize().unwrap_or_else(|_| workspace.to_path_buf());
+    let start = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let inside_workspace = start.starts_with(&root);
+
+    let mut current = Some(start.as_path());
+    while let Some(candidate_dir) = current {
+        let candidate = candidate_dir.join(".vnode.json");
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if !inside_workspace || candidate_dir == root {
+            break;
+        }
+        current = candidate_dir.parent();
+    }
+    None
 }
 
-/// 搜索结果匹配项
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct SearchMatch {
-    /// 匹配的行号（从1开始）
-    pub line: usize,
-    /// 匹配的列号（从0开始）
-    pub column: usize,
-    /// 匹配行的内容（前后各包含一些上下文）
-    pub context: String,
+/// 查询路径所在卷的剩余可用空间（字节）
+/// 
+/// 路径尚不存在时（例如导入的目标目录）使用最近的已存在上级目录所在的卷。
+/// 
+/// # 参数
+/// - `path`: 卷上的任意路径，通常是工作区根目录
+/// 
+/// # 返回
+/// 当前用户可用的剩余字节数
+pub fn get_available_space(path: &Path) -> Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| anyhow::anyhow!("无法解析路径: {}", path.display()))?;
+    available_space_of(existing)
+        .with_context(|| format!("无法查询剩余磁盘空间: {}", existing.display()))
 }
 
-/// 搜索结果
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct SearchResult {
-    /// 文件路径
-    pub file_path: String,
-    /// 匹配项列表
-    pub matches: Vec<SearchMatch>,
+#[cfg(unix)]
+fn available_space_of(path: &Path) -> Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path 是以 NUL 结尾的有效字符串，stat 指向可写的 statvfs 结构
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
 }
 
-/// 在指定目录中搜索关键词
+#[cfg(windows)]
+fn available_space_of(path: &Path) -> Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut available: u64 = 0;
+    // SAFETY: wide 以 NUL 结尾，其余输出参数可以为空
+    let ok = unsafe {
+        windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(available)
+}
+
+/// 确认路径所在卷至少还有 `required` 字节可用，不足时返回错误
+/// 
+/// 用于导入等大批量写入开始之前，避免写到一半磁盘满导致工作区只恢复了一部分
+pub fn ensure_space_available(path: &Path, required: u64) -> Result<()> {
+    let available = get_available_space(path)?;
+    if required > available {
+        anyhow::bail!(
+            "磁盘空间不足：预计需要 {} 字节，可用 {} 字节（{}）",
+            required,
+            available,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// 递归收集工作区内的所有加密文件（.enc）
+/// 
+/// 跳过隐藏文件和隐藏目录（例如 .git、.config），与目录列表和搜索的规则一致。
 /// 
 /// # 参数
-/// - `workspace_path`: 工作区根目录路径
-/// - `query`: 搜索关键词
-/// - `app`: Tauri 应用句柄，用于解密文件
+/// - `workspace`: 工作区根目录
 /// 
 /// # 返回
-/// 返回搜索结果列表
-pub async fn search_files(
-    workspace_path: &str,
-    query: &str,
-    app: &AppHandle,
-) -> Result<Vec<SearchResult>> {
-    let workspace = Path::new(workspace_path);
-    
-    if !workspace.exists() || !workspace.is_dir() {
-        return Ok(Vec::new());
+/// 返回所有 .enc 文件的绝对路径（按路径排序）
+pub fn collect_encrypted_files(workspace: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = visible_files(workspace)
+        .filter(|path| path.to_string_lossy().ends_with(".enc"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// 递归收集工作区内的所有笔记：加密文件（.enc），以及明文目录中的原始文件（见 `is_plaintext_path`）
+/// 
+/// 明文目录中同一笔记同时有原始文件和旧的加密版本时只保留原始文件，与 `read_plaintext_file` 一致。
+/// 搜索、统计、导出等整库操作使用此函数；只检查密文的操作（例如 `scan_vault_integrity`）使用 `collect_encrypted_files`。
+/// 
+/// # 返回
+/// 返回文件的绝对路径（按路径排序）
+pub fn collect_note_files(workspace: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = visible_files(workspace)
+        .filter(|path| {
+            path.to_string_lossy().ends_with(".enc")
+                || is_plaintext_path(&workspace_relative_path(workspace, path))
+        })
+        .collect();
+    files.sort();
+    let plaintext: std::collections::HashSet<PathBuf> = files
+        .iter()
+        .filter(|path| !path.to_string_lossy().ends_with(".enc"))
+        .cloned()
+        .collect();
+    files.retain(|path| match path.to_string_lossy().strip_suffix(".enc") {
+        Some(logical) => !plaintext.contains(Path::new(logical)),
+        None => true,
+    });
+    files
+}
+
+/// 工作区内的所有文件，跳过隐藏文件和隐藏目录
+fn visible_files(workspace: &Path) -> impl Iterator<Item = PathBuf> {
+    walkdir::WalkDir::new(workspace)
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+}
+
+/// 将文件路径转换为相对于工作区的路径（统一使用 `/` 分隔，保留 .enc 后缀）
+pub fn workspace_relative_path(workspace: &Path, path: &Path) -> String {
+    path.strip_prefix(workspace)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 文件或目录重命名/移动后，更新列表中引用它的相对路径（例如收藏列表）
+/// 
+/// 与 `old` 完全相同的路径，以及位于 `old` 目录下的路径都会改为以 `new` 开头
+/// 
+/// # 返回
+/// 列表有变化时返回 true
+pub fn rename_paths_in_list(paths: &mut [String], old: &str, new: &str) -> bool {
+    let old = old.trim_end_matches('/');
+    let new = new.trim_end_matches('/');
+    if old.is_empty() || old == new {
+        return false;
     }
-    
-    let query_lower = query.to_lowercase();
-    let mut results = Vec::new();
-    
-    // 递归搜索目录
-    search_directory_recursive(workspace, workspace, &query_lower, app, &mut results).await?;
-    
-    Ok(results)
+    let mut changed = false;
+    for path in paths.iter_mut() {
+        if path == old {
+            *path = new.to_string();
+            changed = true;
+        } else if let Some(rest) = path.strip_prefix(old).and_then(|rest| rest.strip_prefix('/')) {
+            *path = format!("{}/{}", new, rest);
+            changed = true;
+        }
+    }
+    changed
 }
 
-/// 递归搜索目录
-fn search_directory_recursive<'a>(
-    workspace_root: &'a Path,
-    current_dir: &'a Path,
-    query: &'a str,
-    app: &'a AppHandle,
-    results: &'a mut Vec<SearchResult>,
-) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
-    Box::pin(async move {
-        let mut entries = fs::read_dir(current_dir)
-            .await
-            .with_context(|| format!("无法读取目录: {}", current_dir.display()))?;
-        
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            let name = path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("");
-            
-            // 跳过隐藏文件和 .git 目录
-            if name.starts_with('.') {
-                continue;
-            }
-            
-            let metadata = entry.metadata().await?;
-            
-            if metadata.is_dir() {
-                // 递归搜索子目录
-                search_directory_recursive(workspace_root, &path, query, app, results).await?;
-            } else if metadata.is_file() && name.ends_with(".enc") {
-                // 搜索加密文件
-                if let Ok(matches) = search_file_content(&path, query, app).await {
-                    if !matches.is_empty() {
-                        // 将路径转换为相对于工作区根目录的路径
-                        let relative_path = path
-                            .strip_prefix(workspace_root)
-                            .unwrap_or(&path)
-                            .to_string_lossy()
-                            .to_string();
-                        
-                        results.push(SearchResult {
-                            file_path: relative_path,
-                            matches,
-                        });
-                    }
-                }
-            }
+/// 将加密文件路径转换为相对于工作区的逻辑路径（去掉 .enc 后缀，统一使用 `/` 分隔）
+/// 
+/// # 参数
+/// - `workspace`: 工作区根目录
+/// - `path`: 工作区内的文件路径
+/// 
+/// # 返回
+/// 返回例如 `notes/today.md` 的逻辑路径
+pub fn logical_relative_path(workspace: &Path, path: &Path) -> String {
+    let relative = workspace_relative_path(workspace, path);
+    relative
+        .strip_suffix(".enc")
+        .map(|s| s.to_string())
+        .unwrap_or(relative)
+}
+
+/// 整库解密检查结果
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct VaultIntegrityReport {
+    /// 检查过的 .enc 文件数
+    pub checked: usize,
+    /// 无法用当前主密钥解密的文件（逻辑路径），例如密钥轮换不完整或从其他设备恢复的文件
+    pub undecryptable: Vec<String>,
+}
+
+/// 检查工作区中所有 .enc 文件能否用当前主密钥解密
+/// 
+/// AES-GCM 只有完整解密才能校验认证标签，因此对每个文件做一次试解密（结果直接丢弃）。
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `app`: Tauri 应用句柄，用于获取密钥
+/// 
+/// # 返回
+/// 返回检查结果，前端可据此提示“有 N 个文件无法解密”
+pub async fn scan_vault_integrity(workspace_path: &str, app: &AppHandle) -> Result<VaultIntegrityReport> {
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    check_files_decryptable(Path::new(workspace_path), &master_key).await
+}
+
+async fn check_files_decryptable(workspace: &Path, key: &[u8]) -> Result<VaultIntegrityReport> {
+    let mut report = VaultIntegrityReport {
+        checked: 0,
+        undecryptable: Vec::new(),
+    };
+
+    for file in collect_encrypted_files(workspace) {
+        report.checked += 1;
+        let decryptable = match fs::read(&file).await {
+            Ok(ciphertext) => decrypt_bytes(&ciphertext, key).is_ok(),
+            Err(_) => false,
+        };
+        if !decryptable {
+            report.undecryptable.push(logical_relative_path(workspace, &file));
         }
-        
-        Ok(())
-    })
+    }
+
+    tracing::warn!(
+        "[scan_vault_integrity] 检查 {} 个文件，{} 个无法解密",
+        report.checked,
+        report.undecryptable.len()
+    );
+    Ok(report)
 }
 
-/// 在单个文件中搜索关键词
-async fn search_file_content(
-    file_path: &Path,
-    query: &str,
-    app: &AppHandle,
-) -> Result<Vec<SearchMatch>> {
-    // 读取并解密文件
-    let content = read_encrypted_file(
-        file_path.to_string_lossy().as_ref(),
-        app,
-    ).await?;
+/// 试解密检查密钥时最多抽样的文件数
+const KEY_CHECK_SAMPLE_SIZE: usize = 8;
+
+/// 检查密钥能否解密工作区中的文件（更换或导入密钥前调用）
+/// 
+/// 从排序后的 .enc 文件中均匀抽样试解密，任意一个文件解密成功即认为密钥正确
+/// （AES-GCM 的认证标签保证错误的密钥不会解密成功）。工作区中没有加密文件时返回 true。
+/// 
+/// # 参数
+/// - `workspace`: 工作区根目录
+/// - `key`: 要检查的 32 字节密钥
+pub fn can_decrypt_vault(workspace: &Path, key: &[u8]) -> bool {
+    let files = collect_encrypted_files(workspace);
+    if files.is_empty() {
+        return true;
+    }
+
+    let step = files.len().div_ceil(KEY_CHECK_SAMPLE_SIZE);
+    let decryptable = files
+        .iter()
+        .step_by(step)
+        .any(|file| std::fs::read(file).is_ok_and(|ciphertext| decrypt_bytes(&ciphertext, key).is_ok()));
+    if !decryptable {
+        tracing::warn!("[can_decrypt_vault] 抽样的文件都无法用该密钥解密");
+    }
+    decryptable
+}
+
+/// 整库解密（搜索、统计、导出）时默认同时解密的最大文件数
+/// 
+/// 移动端内存有限，默认只并发 2 个；桌面端默认 8 个
+#[cfg(any(target_os = "android", target_os = "ios"))]
+pub const DEFAULT_DECRYPT_CONCURRENCY: usize = 2;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub const DEFAULT_DECRYPT_CONCURRENCY: usize = 8;
+
+static DECRYPT_CONCURRENCY: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(DEFAULT_DECRYPT_CONCURRENCY);
+
+/// 设置整库解密的并发上限（进程全局，随工作区配置生效；最小为 1）
+pub fn set_decrypt_concurrency(max_concurrent: usize) {
+    DECRYPT_CONCURRENCY.store(max_concurrent.max(1), std::sync::atomic::Ordering::Relaxed);
+}
+
+/// 当前的整库解密并发上限
+pub fn decrypt_concurrency() -> usize {
+    DECRYPT_CONCURRENCY.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// 整库统计信息
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct VaultStats {
+    /// 成功解密的笔记数
+    pub note_count: usize,
+    /// 总字数（中日韩文字按字计数，其他文字按空白分隔的词计数）
+    pub word_count: usize,
+    /// 笔记文件的总大小（字节，加密笔记按密文大小计算）
+    pub total_size: u64,
+    /// 最近编辑的笔记（逻辑路径）
+    pub last_edited_path: Option<String>,
+    /// 最近编辑时间（RFC 3339）
+    pub last_edited_at: Option<String>,
+    /// 无法解密而跳过的文件数
+    pub skipped: usize,
+}
+
+/// 统计文本字数：中日韩文字每个字计为一个词，其他连续的非空白字符计为一个词
+pub fn count_words(text: &str) -> usize {
+    let is_cjk = |c: char| {
+        matches!(c as u32,
+            0x3040..=0x30FF     // 平假名、片假名
+            | 0x3400..=0x4DBF   // 中日韩统一表意文字扩展 A
+            | 0x4E00..=0x9FFF   // 中日韩统一表意文字
+            | 0xAC00..=0xD7AF   // 韩文音节
+            | 0xF900..=0xFAFF)  // 中日韩兼容表意文字
+    };
+
+    let mut count = 0;
+    let mut in_word = false;
+    for c in text.chars() {
+        if is_cjk(c) {
+            count += 1;
+            in_word = false;
+        } else if c.is_whitespace() {
+            in_word = false;
+        } else if !in_word {
+            count += 1;
+            in_word = true;
+        }
+    }
+    count
+}
+
+/// 获取整库统计信息（笔记数、总字数、总大小、最近编辑的笔记）
+/// 
+/// 逐个解密所有笔记（明文目录中的笔记直接读取），同时解密的文件数受 `decrypt_concurrency()` 限制；
+/// 无法解密的文件计入 `skipped` 而不是让整个统计失败。
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `app`: Tauri 应用句柄，用于获取密钥
+pub async fn get_vault_stats(workspace_path: &str, app: &AppHandle) -> Result<VaultStats> {
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    collect_vault_stats(Path::new(workspace_path), &master_key).await
+}
+
+/// 解密后的文件（交给 `decrypt_all_with_limit` 的回调）
+pub struct DecryptedFile<'a> {
+    pub path: &'a Path,
+    pub size: u64,
+    pub bytes: Vec<u8>,
+    pub meta: Option<NoteMeta>,
+}
+
+/// 并发解密一组文件，并对每个文件执行 `f`
+/// 
+/// 整库操作（搜索、统计、导出）统一使用此函数：同时解密的文件数不超过 `max_concurrent`，
+/// 解密后的内容在回调结束后立即释放，避免大型工作区在移动端因内存压力崩溃。
+/// 不带 .enc 后缀的文件（明文目录中的笔记，见 `collect_note_files`）直接读取原始内容。
+/// 
+/// # 参数
+/// - `files`: 要解密的文件
+/// - `max_concurrent`: 同时解密的最大文件数（通常传 `decrypt_concurrency()`）
+/// - `key`: 主加密密钥
+/// - `f`: 对每个解密成功的文件执行的回调
+/// 
+/// # 返回
+/// 按路径排序的 (文件路径, 回调结果)；无法读取或解密的文件结果为 None
+pub async fn decrypt_all_with_limit<T, F>(
+    files: Vec<PathBuf>,
+    max_concurrent: usize,
+    key: &[u8],
+    f: F,
+) -> Vec<(PathBuf, Option<T>)>
+where
+    T: Send + 'static,
+    F: Fn(DecryptedFile) -> T + Send + Sync + 'static,
+{
+    let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(max_concurrent.max(1)));
+    let key = std::sync::Arc::new(key.to_vec());
+    let f = std::sync::Arc::new(f);
+    let mut tasks = tokio::task::JoinSet::new();
+
+    for file in files {
+        let semaphore = semaphore.clone();
+        let key = key.clone();
+        let f = f.clone();
+        tasks.spawn(async move {
+            let result = async {
+                let _permit = semaphore.acquire_owned().await.ok()?;
+                let ciphertext = fs::read(&file).await.ok()?;
+                let size = ciphertext.len() as u64;
+                let (bytes, meta) = if file.to_string_lossy().ends_with(".enc") {
+                    decrypt_note_bytes(&ciphertext, &key).ok()?
+                } else {
+                    (ciphertext, None)
+                };
+                Some(f(DecryptedFile {
+                    path: &file,
+                    size,
+                    bytes,
+                    meta,
+                }))
+            }
+            .await;
+            (file, result)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok(entry) = joined {
+            results.push(entry);
+        }
+    }
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    results
+}
+
+/// 解密后的笔记（交给 `map_decrypted_notes` 的回调）
+struct DecryptedNote<'a> {
+    path: &'a Path,
+    size: u64,
+    content: String,
+    meta: Option<NoteMeta>,
+}
+
+/// 解密工作区中的所有笔记，并对每篇笔记执行 `f`
+/// 
+/// 基于 `decrypt_all_with_limit`，并发上限为 `decrypt_concurrency()`；内容不是 UTF-8 文本的文件结果为 None。
+async fn map_decrypted_notes<T, F>(workspace: &Path, key: &[u8], f: F) -> Vec<(PathBuf, Option<T>)>
+where
+    T: Send + 'static,
+    F: Fn(DecryptedNote) -> T + Send + Sync + 'static,
+{
+    let files = collect_note_files(workspace);
+    decrypt_all_with_limit(files, decrypt_concurrency(), key, move |file| {
+        let content = String::from_utf8(file.bytes).ok()?;
+        Some(f(DecryptedNote {
+            path: file.path,
+            size: file.size,
+            content,
+            meta: file.meta,
+        }))
+    })
+    .await
+    .into_iter()
+    .map(|(path, result)| (path, result.flatten()))
+    .collect()
+}
+
+async fn collect_vault_stats(workspace: &Path, key: &[u8]) -> Result<VaultStats> {
+    let notes = map_decrypted_notes(workspace, key, |note| {
+        // 优先使用笔记内嵌的修改时间（同步后文件系统时间不可靠）
+        let modified = note
+            .meta
+            .and_then(|m| chrono::DateTime::parse_from_rfc3339(&m.modified).ok())
+            .map(|time| time.with_timezone(&chrono::Utc))
+            .or_else(|| {
+                let mtime = std::fs::metadata(note.path).ok()?.modified().ok()?;
+                Some(chrono::DateTime::<chrono::Utc>::from(mtime))
+            });
+        (note.size, count_words(&note.content), modified)
+    })
+    .await;
+
+    let mut stats = VaultStats {
+        note_count: 0,
+        word_count: 0,
+        total_size: 0,
+        last_edited_path: None,
+        last_edited_at: None,
+        skipped: 0,
+    };
+    let mut latest: Option<(chrono::DateTime<chrono::Utc>, PathBuf)> = None;
+
+    for (file, result) in notes {
+        match result {
+            Some((size, words, modified)) => {
+                stats.note_count += 1;
+                stats.word_count += words;
+                stats.total_size += size;
+                if let Some(modified) = modified {
+                    if latest.as_ref().is_none_or(|(time, _)| modified > *time) {
+                        latest = Some((modified, file));
+                    }
+                }
+            }
+            None => stats.skipped += 1,
+        }
+    }
+
+    if let Some((time, file)) = latest {
+        stats.last_edited_path = Some(logical_relative_path(workspace, &file));
+        stats.last_edited_at = Some(time.to_rfc3339());
+    }
+
+    tracing::info!(
+        "[get_vault_stats] {} 篇笔记，{} 字，跳过 {} 个文件",
+        stats.note_count,
+        stats.word_count,
+        stats.skipped
+    );
+    Ok(stats)
+}
+
+/// 标签索引：标签 -> 带有该标签的笔记（相对于工作区的路径）
+pub type TagIndex = std::collections::BTreeMap<String, Vec<String>>;
+
+/// 标签索引缓存（以工作区路径和 HEAD 提交为键）
+struct TagIndexCache {
+    workspace: PathBuf,
+    head: String,
+    index: TagIndex,
+}
+
+static TAG_INDEX_CACHE: std::sync::Mutex<Option<TagIndexCache>> = std::sync::Mutex::new(None);
+
+/// 从笔记开头的 YAML front-matter 中解析标签
+/// 
+/// 支持 `tags: [a, b]`、`tags: a, b` 以及
+/// ```text
+/// tags:
+///   - a
+///   - b
+/// ```
+/// 三种写法（`tag:` 同样识别），标签去掉引号和开头的 `#`。
+pub fn parse_front_matter_tags(content: &str) -> Vec<String> {
+    let mut lines = content.trim_start_matches('\u{feff}').lines();
+    if lines.next().map(|l| l.trim_end()) != Some("---") {
+        return Vec::new();
+    }
+    let front_matter: Vec<&str> = lines
+        .take_while(|l| !matches!(l.trim_end(), "---" | "..."))
+        .collect();
+
+    let clean = |tag: &str| {
+        tag.trim()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .trim_start_matches('#')
+            .trim()
+            .to_string()
+    };
+
+    let mut tags: Vec<String> = Vec::new();
+    for (i, line) in front_matter.iter().enumerate() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        if line.starts_with(char::is_whitespace) || !matches!(key.trim().to_lowercase().as_str(), "tags" | "tag") {
+            continue;
+        }
+        let value = value.trim();
+        if value.is_empty() {
+            // 块列表写法：后续以 `- ` 开头的行都是标签
+            tags.extend(
+                front_matter[i + 1..]
+                    .iter()
+                    .map(|l| l.trim())
+                    .take_while(|l| l.starts_with('-'))
+                    .map(|l| clean(&l[1..])),
+            );
+        } else {
+            let value = value.trim_start_matches('[').trim_end_matches(']');
+            tags.extend(
+                value
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .map(&clean),
+            );
+        }
+        break;
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    tags.retain(|tag| !tag.is_empty() && seen.insert(tag.clone()));
+    tags
+}
+
+/// 内容完全相同的一组文件
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DuplicateGroup {
+    /// 明文内容哈希（与 `file_content_hash` 一致）
+    pub hash: String,
+    /// 明文字节数
+    pub size: u64,
+    /// 文件路径（相对于工作区，含 .enc 后缀，已排序）
+    pub paths: Vec<String>,
+}
+
+/// 查找内容相同的笔记
+/// 
+/// 按解密后的内容哈希分组，因此 nonce 不同的密文只要明文相同也会被识别为重复；
+/// 空文件和无法解密的文件不参与比较。
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `app`: Tauri 应用句柄，用于获取密钥
+/// 
+/// # 返回
+/// 返回包含两个及以上文件的分组，按第一个路径排序
+pub async fn find_duplicates(workspace_path: &str, app: &AppHandle) -> Result<Vec<DuplicateGroup>> {
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    Ok(collect_duplicates(Path::new(workspace_path), &master_key).await)
+}
+
+async fn collect_duplicates(workspace: &Path, key: &[u8]) -> Vec<DuplicateGroup> {
+    let hashes = decrypt_all_with_limit(
+        collect_note_files(workspace),
+        decrypt_concurrency(),
+        key,
+        |file| {
+            if file.bytes.is_empty() {
+                return None;
+            }
+            content_hash(&file.bytes).ok().map(|hash| (hash, file.bytes.len() as u64))
+        },
+    )
+    .await;
+
+    let mut groups: std::collections::HashMap<String, DuplicateGroup> = std::collections::HashMap::new();
+    for (file, hashed) in hashes {
+        let Some((hash, size)) = hashed.flatten() else {
+            continue;
+        };
+        groups
+            .entry(hash.clone())
+            .or_insert_with(|| DuplicateGroup {
+                hash,
+                size,
+                paths: Vec::new(),
+            })
+            .paths
+            .push(workspace_relative_path(workspace, &file));
+    }
+
+    let mut duplicates: Vec<DuplicateGroup> = groups
+        .into_values()
+        .filter(|group| group.paths.len() > 1)
+        .collect();
+    duplicates.sort_by(|a, b| a.paths.cmp(&b.paths));
+    tracing::info!("[find_duplicates] 找到 {} 组重复笔记", duplicates.len());
+    duplicates
+}
+
+/// 建立标签索引
+/// 
+/// 解密每篇笔记并解析 front-matter 中的标签。索引以 HEAD 提交为键缓存：
+/// 工作区没有未提交的更改且 HEAD 未变化时直接返回缓存，不重新解密。
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `app`: Tauri 应用句柄，用于获取密钥
+/// 
+/// # 返回
+/// 返回标签到笔记路径（相对于工作区，含 .enc 后缀）的映射
+pub async fn index_tags(workspace_path: &str, app: &AppHandle) -> Result<TagIndex> {
+    let workspace = Path::new(workspace_path);
+
+    // 只有工作区干净时 HEAD 才能代表笔记内容
+    let cache_key = match crate::git::get_repository_status(workspace) {
+        Ok(status) if status.is_clean => crate::git::get_head_commit(workspace)
+            .ok()
+            .flatten()
+            .map(|head| head.sha),
+        _ => None,
+    };
+    if let Some(head) = &cache_key {
+        let cache = TAG_INDEX_CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(cached) = cache.as_ref().filter(|c| c.workspace == workspace && &c.head == head) {
+            tracing::info!("[index_tags] 使用缓存的标签索引（HEAD {}）", head);
+            return Ok(cached.index.clone());
+        }
+    }
+
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let index = build_tag_index(workspace, &master_key).await;
+
+    if let Some(head) = cache_key {
+        *TAG_INDEX_CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(TagIndexCache {
+            workspace: workspace.to_path_buf(),
+            head,
+            index: index.clone(),
+        });
+    }
+    Ok(index)
+}
+
+async fn build_tag_index(workspace: &Path, key: &[u8]) -> TagIndex {
+    let notes = map_decrypted_notes(workspace, key, |note| parse_front_matter_tags(&note.content)).await;
+
+    let mut index = TagIndex::new();
+    for (file, tags) in notes {
+        let relative = workspace_relative_path(workspace, &file);
+        for tag in tags.unwrap_or_default() {
+            index.entry(tag).or_default().push(relative.clone());
+        }
+    }
+    tracing::info!("[index_tags] 共 {} 个标签", index.len());
+    index
+}
+
+/// 反向链接索引
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct BacklinkIndex {
+    /// 笔记路径 -> 链接到它的笔记路径（均为相对于工作区、含 .enc 后缀的路径）
+    pub backlinks: std::collections::BTreeMap<String, Vec<String>>,
+    /// 找不到目标笔记的链接：链接目标 -> 包含该链接的笔记路径
+    pub dangling: std::collections::BTreeMap<String, Vec<String>>,
+}
+
+/// 提取笔记中的 wiki 链接 `[[目标]]`
+/// 
+/// 返回链接目标（去掉 `|别名` 和 `#标题` 部分），不跨行匹配。
+pub fn extract_wiki_links(content: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
+        };
+        let inner = &after[..end];
+        if !inner.contains('\n') {
+            let target = inner.split(['|', '#']).next().unwrap_or("").trim();
+            if !target.is_empty() {
+                links.push(target.to_string());
+            }
+            rest = &after[end + 2..];
+        } else {
+            rest = after;
+        }
+    }
+    links
+}
+
+/// wiki 链接的匹配键：去掉 .enc 和笔记扩展名（如 .md），统一小写
+fn wiki_link_key(name: &str) -> String {
+    let name = name.trim().trim_start_matches("./").trim_end_matches(".enc");
+    let name = Path::new(name)
+        .with_extension("")
+        .to_string_lossy()
+        .replace('\\', "/");
+    name.to_lowercase()
+}
+
+/// 将 wiki 链接目标解析为笔记路径的查找表
+/// 
+/// 同时登记完整相对路径（`notes/foo`）和文件名（`foo`）两种写法；
+/// 文件名重复时按路径排序取第一个。
+fn wiki_link_targets(notes: &[String]) -> std::collections::HashMap<String, String> {
+    let mut targets = std::collections::HashMap::new();
+    for note in notes {
+        targets.entry(wiki_link_key(note)).or_insert_with(|| note.clone());
+    }
+    for note in notes {
+        let name = note.rsplit('/').next().unwrap_or(note);
+        targets.entry(wiki_link_key(name)).or_insert_with(|| note.clone());
+    }
+    targets
+}
+
+/// 建立反向链接索引
+/// 
+/// 解密所有笔记并提取其中的 `[[目标]]` 链接，按文件名或相对路径（不区分大小写、可省略扩展名）
+/// 解析到具体笔记，返回每篇笔记被哪些笔记链接。
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `app`: Tauri 应用句柄，用于获取密钥
+pub async fn build_backlinks(workspace_path: &str, app: &AppHandle) -> Result<BacklinkIndex> {
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    Ok(collect_backlinks(Path::new(workspace_path), &master_key).await)
+}
+
+async fn collect_backlinks(workspace: &Path, key: &[u8]) -> BacklinkIndex {
+    let notes = map_decrypted_notes(workspace, key, |note| extract_wiki_links(&note.content)).await;
+    let paths: Vec<String> = notes.iter().map(|(file, _)| workspace_relative_path(workspace, file)).collect();
+    let targets = wiki_link_targets(&paths);
+
+    let mut index = BacklinkIndex::default();
+    for (source, (_, links)) in paths.iter().zip(notes) {
+        for link in links.unwrap_or_default() {
+            let entry = match targets.get(&wiki_link_key(&link)) {
+                Some(target) if target == source => continue, // 自引用不算反向链接
+                Some(target) => index.backlinks.entry(target.clone()).or_default(),
+                None => index.dangling.entry(link).or_default(),
+            };
+            if !entry.contains(source) {
+                entry.push(source.clone());
+            }
+        }
+    }
+    index
+}
+
+/// 改写内容中的 wiki 链接目标
+/// 
+/// 对每个 `[[目标|别名]]` / `[[目标#标题]]` 调用 `replace(目标)`，返回 Some 时替换目标部分，
+/// 别名和标题部分保持不变。
+/// 
+/// # 返回
+/// 内容有变化时返回改写后的内容，否则返回 None
+pub fn rewrite_wiki_links<F>(content: &str, replace: F) -> Option<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut output = String::with_capacity(content.len());
+    let mut changed = false;
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
+        };
+        let inner = &after[..end];
+        if inner.contains('\n') {
+            output.push_str(&rest[..start + 2]);
+            rest = after;
+            continue;
+        }
+        let split = inner.find(['|', '#']).unwrap_or(inner.len());
+        let (target, suffix) = inner.split_at(split);
+        output.push_str(&rest[..start + 2]);
+        match replace(target.trim()) {
+            Some(new_target) => {
+                output.push_str(&new_target);
+                changed = true;
+            }
+            None => output.push_str(target),
+        }
+        output.push_str(suffix);
+        output.push_str("]]");
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+    changed.then_some(output)
+}
+
+/// 计算重命名笔记后需要改写的笔记内容
+/// 
+/// `old_rel` / `new_rel` 为重命名前后相对于工作区的路径（含 .enc 后缀），调用时文件应已重命名。
+/// 原先解析到旧笔记的链接改为指向新笔记：使用路径写法的链接改为新路径，使用文件名写法的链接改为新文件名
+/// （新文件名与其他笔记重名时改用路径写法），原链接带扩展名的保留扩展名。
+/// 
+/// # 返回
+/// 需要写回的 (文件路径, 新内容, 原元数据, 原内容)
+async fn rewrite_links_after_rename(
+    workspace: &Path,
+    old_rel: &str,
+    new_rel: &str,
+    key: &[u8],
+) -> Vec<(PathBuf, String, Option<NoteMeta>, String)> {
+    let current: Vec<String> = collect_note_files(workspace)
+        .iter()
+        .map(|file| workspace_relative_path(workspace, file))
+        .collect();
+    let mut before: Vec<String> = current
+        .iter()
+        .map(|note| if note == new_rel { old_rel.to_string() } else { note.clone() })
+        .collect();
+    before.sort();
+    let targets_before = wiki_link_targets(&before);
+    let targets_after = wiki_link_targets(&current);
+
+    let new_logical = new_rel.strip_suffix(".enc").unwrap_or(new_rel).to_string();
+    let new_name = new_logical.rsplit('/').next().unwrap_or(&new_logical).to_string();
+    let name_is_unique = targets_after.get(&wiki_link_key(&new_name)).map(|t| t.as_str()) == Some(new_rel);
+    tracing::info!("[rename_with_link_update] {} -> {}", old_rel, new_rel);
+    let old_rel = old_rel.to_string();
+
+    let notes = map_decrypted_notes(workspace, key, move |note| {
+        let content = rewrite_wiki_links(&note.content, |target| {
+            if targets_before.get(&wiki_link_key(target)) != Some(&old_rel) {
+                return None;
+            }
+            let base = if target.contains('/') || !name_is_unique {
+                new_logical.clone()
+            } else {
+                new_name.clone()
+            };
+            // 原链接省略了扩展名时，新链接同样省略
+            let has_extension = Path::new(target.trim_end_matches(".enc")).extension().is_some();
+            let replacement = if has_extension {
+                base
+            } else {
+                Path::new(&base).with_extension("").to_string_lossy().replace('\\', "/")
+            };
+            (replacement != target).then_some(replacement)
+        })?;
+        Some((content, note.meta, note.content))
+    })
+    .await;
+
+    notes
+        .into_iter()
+        .filter_map(|(file, result)| result.flatten().map(|(content, meta, original)| (file, content, meta, original)))
+        .collect()
+}
+
+/// 写回改写了链接的笔记，返回实际写回的数量
+/// 
+/// 每个文件在持有文件锁的情况下重新读取：内容在计算改写之后被修改过的文件跳过（记录警告），
+/// 不能用旧内容覆盖新的编辑。
+async fn apply_link_updates(
+    updates: &[(PathBuf, String, Option<NoteMeta>, String)],
+    key: &[u8],
+    compress: bool,
+) -> Result<usize> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut written = 0;
+    for (file, content, meta, original) in updates {
+        let _lock = lock_path(file).await;
+        let encrypted = file.to_string_lossy().ends_with(".enc");
+        let bytes = fs::read(file)
+            .await
+            .with_context(|| format!("无法读取文件: {}", file.display()))?;
+        let current = if encrypted {
+            decrypt_note(&bytes, key).ok().map(|(current, _)| current)
+        } else {
+            String::from_utf8(bytes).ok()
+        };
+        if current.as_deref() != Some(original.as_str()) {
+            tracing::warn!("[rename_with_link_update] {} 在改写期间被修改，跳过", file.display());
+            continue;
+        }
+
+        let output = if encrypted {
+            let meta = NoteMeta {
+                created: meta.as_ref().map(|m| m.created.clone()).unwrap_or_else(|| now.clone()),
+                modified: now.clone(),
+            };
+            encrypt_note(content, &meta, compress, key).context("加密内容失败")?
+        } else {
+            content.as_bytes().to_vec()
+        };
+        fs::write(file, &output)
+            .await
+            .with_context(|| format!("无法写入文件: {}", file.display()))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// 重命名笔记并改写全库中指向它的 `[[链接]]`
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录
+/// - `old_path`: 原笔记路径（.enc 文件）
+/// - `new_path`: 新笔记路径（.enc 文件）
+/// - `app`: Tauri 应用句柄，用于获取密钥和配置
+/// 
+/// # 返回
+/// 返回链接被改写的笔记数量
+pub async fn rename_with_link_update(
+    workspace_path: &str,
+    old_path: &str,
+    new_path: &str,
+    app: &AppHandle,
+) -> Result<usize> {
+    let workspace = Path::new(workspace_path);
+    if !old_path.ends_with(".enc") || !new_path.ends_with(".enc") {
+        anyhow::bail!("只能重命名笔记文件（.enc）: {} -> {}", old_path, new_path);
+    }
+    if !Path::new(old_path).is_file() {
+        anyhow::bail!("笔记不存在: {}", old_path);
+    }
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+
+    rename_file_or_directory(old_path, new_path).await?;
+
+    let old_rel = workspace_relative_path(workspace, Path::new(old_path));
+    let new_rel = workspace_relative_path(workspace, Path::new(new_path));
+    let updates = rewrite_links_after_rename(workspace, &old_rel, &new_rel, &master_key).await;
+
+    let compress = crate::commands::read_workspace_config_or_default(app, "rename_with_link_update")
+        .await
+        .compress_notes;
+    let written = apply_link_updates(&updates, &master_key, compress).await?;
+
+    tracing::info!("[rename_with_link_update] 改写了 {} 篇笔记中的链接", written);
+    Ok(written)
+}
+
+/// 文件信息结构
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FileInfo {
+    pub name: String,
+    pub path: String,
+    pub is_directory: bool,
+    pub is_file: bool,
+}
+
+/// 搜索结果匹配项
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SearchMatch {
+    /// 匹配的行号（从1开始）
+    pub line: usize,
+    /// 匹配的列号（从0开始）
+    pub column: usize,
+    /// 匹配行的内容（前后各包含一些上下文）
+    pub context: String,
+}
+
+/// 搜索结果
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SearchResult {
+    /// 文件路径
+    pub file_path: String,
+    /// 匹配项列表
+    pub matches: Vec<SearchMatch>,
+}
+
+/// 在指定目录中搜索关键词
+/// 
+/// # 参数
+/// - `workspace_path`: 工作区根目录路径
+/// - `query`: 搜索关键词
+/// - `app`: Tauri 应用句柄，用于解密文件
+/// 
+/// # 返回
+/// 返回搜索结果列表
+pub async fn search_files(
+    workspace_path: &str,
+    query: &str,
+    app: &AppHandle,
+) -> Result<Vec<SearchResult>> {
+    let workspace = Path::new(workspace_path);
+    
+    if !workspace.exists() || !workspace.is_dir() {
+        return Ok(Vec::new());
+    }
+    
+    let master_key = get_or_create_master_key(app)
+        .await
+        .context("无法获取主加密密钥")?;
+    let query_lower = query.to_lowercase();
+    
+    // 并发解密受 decrypt_concurrency() 限制，每个文件的明文在搜索完后立即释放
+    let matches = decrypt_all_with_limit(
+        collect_note_files(workspace),
+        decrypt_concurrency(),
+        &master_key,
+        move |file| search_content(&String::from_utf8_lossy(&file.bytes), &query_lower),
+    )
+    .await;
+
+    let results = matches
+        .into_iter()
+        .filter_map(|(path, matches)| {
+            let matches = matches.filter(|m| !m.is_empty())?;
+            Some(SearchResult {
+                file_path: workspace_relative_path(workspace, &path),
+                matches,
+            })
+        })
+        .collect();
     
+    Ok(results)
+}
+
+/// 在单个文件的内容中搜索关键词（`query` 已转换为小写）
+pub(crate) fn search_content(content: &str, query: &str) -> Vec<SearchMatch> {
     let mut matches = Vec::new();
     let lines: Vec<&str> = content.lines().collect();
     
@@ -564,6 +2529,587 @@ async fn search_file_content(
         }
     }
     
-    Ok(matches)
+    matches
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_workspace, write_note};
+
+    #[tokio::test]
+    async fn test_list_directory_shows_nfc_names() {
+        let root = temp_workspace("nfc");
+        let nfd = "re\u{301}sume\u{301}";
+        std::fs::create_dir_all(root.join(nfd)).unwrap();
+
+        let entries = list_directory(root.to_str().unwrap()).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "r\u{e9}sum\u{e9}");
+        assert!(Path::new(&entries[0].path).is_dir());
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_folders() {
+        let _guard = PLAINTEXT_FOLDERS_TEST_LOCK.lock().await;
+        set_plaintext_folders(&["Public/".to_string(), "shared/*.txt".to_string()]);
+        assert!(is_plaintext_path("Public/a.md"));
+        assert!(is_plaintext_path("Public/sub/a.md.enc"));
+        assert!(is_plaintext_path("shared/list.txt"));
+        assert!(!is_plaintext_path("shared/list.md"));
+        assert!(!is_plaintext_path("Private/a.md"));
+        assert!(!is_plaintext_path("PublicNotes/a.md"));
+        set_plaintext_folders(&[]);
+        assert!(!is_plaintext_path("Public/a.md"));
+
+        let root = temp_workspace("plaintext");
+        let note = root.join("Public/a.md");
+        std::fs::create_dir_all(root.join("Public")).unwrap();
+        std::fs::write(root.join("Public/a.md.enc"), b"old ciphertext").unwrap();
+
+        write_plaintext_file(note.to_str().unwrap(), "hello").await.unwrap();
+        assert_eq!(std::fs::read_to_string(&note).unwrap(), "hello");
+        assert!(!root.join("Public/a.md.enc").exists());
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_folder_notes_in_vault_operations() {
+        let _guard = PLAINTEXT_FOLDERS_TEST_LOCK.lock().await;
+        set_plaintext_folders(&["Public".to_string()]);
+        let workspace = temp_workspace("plaintext_notes");
+        std::fs::create_dir_all(workspace.join("Public")).unwrap();
+        let key = [7u8; 32];
+        write_note(&workspace, "notes/secret.md.enc", "same text", &key);
+        std::fs::write(workspace.join("Public/copy.md"), "same text").unwrap();
+        std::fs::write(workspace.join("Public/open.md"), "open").unwrap();
+        // 目录设为明文之前留下的加密版本被原始文件取代；明文目录之外的原始文件不是笔记
+        write_note(&workspace, "Public/open.md.enc", "stale", &key);
+        std::fs::write(workspace.join("loose.md"), "not a note").unwrap();
+
+        assert_eq!(
+            collect_note_files(&workspace),
+            vec![workspace.join("Public/copy.md"), workspace.join("Public/open.md"), workspace.join("notes/secret.md.enc")]
+        );
+        let stats = collect_vault_stats(&workspace, &key).await.unwrap();
+        assert_eq!((stats.note_count, stats.word_count, stats.skipped), (3, 5, 0));
+        let duplicates = collect_duplicates(&workspace, &key).await;
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].paths, vec!["Public/copy.md", "notes/secret.md.enc"]);
+
+        // 批量写入：明文目录中的文件不加密，并删除旧的加密版本
+        let files = vec![
+            ("Public/open.md".to_string(), "rewritten".to_string()),
+            ("notes/new.md".to_string(), "new".to_string()),
+        ];
+        let written = write_files_with_key(&workspace, &files, &key, false).await.unwrap();
+        assert_eq!(written, vec!["Public/open.md", "notes/new.md.enc", "Public/open.md.enc"]);
+        assert_eq!(std::fs::read_to_string(workspace.join("Public/open.md")).unwrap(), "rewritten");
+        assert!(!workspace.join("Public/open.md.enc").exists());
+        assert!(!workspace.join("notes/new.md").exists());
+
+        set_plaintext_folders(&[]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_directory_refuses_repo_root_and_git_dir() {
+        let root = temp_workspace("protect");
+        let notes = root.join("notes");
+        std::fs::create_dir_all(root.join(".git/objects")).unwrap();
+        std::fs::create_dir_all(&notes).unwrap();
+
+        assert!(delete_directory(root.to_str().unwrap()).await.is_err());
+        assert!(delete_directory(root.join(".git").to_str().unwrap()).await.is_err());
+        assert!(delete_directory(root.join(".git/objects").to_str().unwrap()).await.is_err());
+        assert!(root.join(".git/objects").exists());
+
+        delete_directory(notes.to_str().unwrap()).await.unwrap();
+        assert!(!notes.exists());
+    }
+
+    #[tokio::test]
+    async fn test_check_files_decryptable_reports_foreign_key_files() {
+        let workspace = temp_workspace("integrity");
+        let key = [7u8; 32];
+        let other_key = [9u8; 32];
+        write_note(&workspace, "notes/ok.md.enc", "fine", &key);
+        write_note(&workspace, "notes/foreign.md.enc", "other", &other_key);
+        std::fs::write(workspace.join("truncated.md.enc"), b"short").unwrap();
+
+        let report = check_files_decryptable(&workspace, &key).await.unwrap();
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.undecryptable, vec!["notes/foreign.md", "truncated.md"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_decrypt_all_with_limit_bounds_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let workspace = temp_workspace("decrypt_limit");
+        let key = [7u8; 32];
+        for i in 0..8 {
+            write_note(&workspace, &format!("{}.md.enc", i), "needle", &key);
+        }
+        write_note(&workspace, "foreign.md.enc", "x", &[9u8; 32]);
+
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (active_cb, peak_cb) = (active.clone(), peak.clone());
+        let results = decrypt_all_with_limit(collect_encrypted_files(&workspace), 2, &key, move |file| {
+            let now = active_cb.fetch_add(1, Ordering::SeqCst) + 1;
+            peak_cb.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            active_cb.fetch_sub(1, Ordering::SeqCst);
+            search_content(&String::from_utf8_lossy(&file.bytes), "needle").len()
+        })
+        .await;
+
+        assert_eq!(results.len(), 9);
+        assert_eq!(results.iter().filter(|(_, r)| *r == Some(1)).count(), 8);
+        assert!(results.iter().any(|(path, r)| path.ends_with("foreign.md.enc") && r.is_none()));
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn test_search_content_reports_line_and_context() {
+        let matches = search_content("first\nHello world\nlast", "hello");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, 2);
+        assert_eq!(matches[0].column, 0);
+        assert_eq!(matches[0].context, "  first\n> Hello world\n  last");
+        assert!(search_content("nothing here", "hello").is_empty());
+    }
+
+    #[test]
+    fn test_content_hash_ignores_nonce_and_meta() {
+        let key = [7u8; 32];
+        let meta = |modified: &str| NoteMeta {
+            created: "2024-01-01T00:00:00+00:00".to_string(),
+            modified: modified.to_string(),
+        };
+        let first = encrypt_note("same text", &meta("2024-01-01T00:00:00+00:00"), false, &key).unwrap();
+        let second = encrypt_note("same text", &meta("2025-01-01T00:00:00+00:00"), true, &key).unwrap();
+        assert_ne!(first, second);
+
+        let hash = |ciphertext: &[u8]| content_hash(&decrypt_note_bytes(ciphertext, &key).unwrap().0).unwrap();
+        assert_eq!(hash(&first), hash(&second));
+        assert_eq!(hash(&first).len(), 40);
+        assert_ne!(content_hash(b"same text").unwrap(), content_hash(b"other text").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_find_duplicates_groups_identical_plaintext() {
+        let workspace = temp_workspace("duplicates");
+        let key = [7u8; 32];
+        write_note(&workspace, "a.md.enc", "same", &key);
+        write_note(&workspace, "notes/copy.md.enc", "same", &key);
+        write_note(&workspace, "unique.md.enc", "different", &key);
+        write_note(&workspace, "empty1.md.enc", "", &key);
+        write_note(&workspace, "empty2.md.enc", "", &key);
+
+        let groups = collect_duplicates(&workspace, &key).await;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].paths, vec!["a.md.enc", "notes/copy.md.enc"]);
+        assert_eq!(groups[0].size, 4);
+        assert_eq!(groups[0].hash, content_hash(b"same").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_read_files_batch_reports_errors_per_file() {
+        let workspace = temp_workspace("batch");
+        let key = [7u8; 32];
+        write_note(&workspace, "a.md.enc", "first", &key);
+        write_note(&workspace, "notes/b.md.enc", "second", &key);
+        write_note(&workspace, "foreign.md.enc", "x", &[9u8; 32]);
+
+        let paths: Vec<String> = ["a.md", "notes/b.md.enc", "foreign.md", "missing.md", "../outside.md"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let results = read_files_with_key(&workspace, &paths, &key).await;
+        assert_eq!(results.len(), 5);
+        assert_eq!(results["a.md"].content.as_deref(), Some("first"));
+        assert_eq!(results["notes/b.md.enc"].content.as_deref(), Some("second"));
+        for failed in ["foreign.md", "missing.md", "../outside.md"] {
+            assert!(results[failed].content.is_none());
+            assert!(results[failed].error.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_files_rolls_back_on_failure() {
+        let workspace = temp_workspace("write_files");
+        let key = [7u8; 32];
+        let files = vec![
+            ("a.md".to_string(), "first".to_string()),
+            ("notes/b.md".to_string(), "second".to_string()),
+        ];
+        let written = write_files_with_key(&workspace, &files, &key, false).await.unwrap();
+        assert_eq!(written, vec!["a.md.enc", "notes/b.md.enc"]);
+        let read = |path: &str| decrypt_note(&std::fs::read(workspace.join(path)).unwrap(), &key).unwrap().0;
+        assert_eq!(read("notes/b.md.enc"), "second");
+
+        // 第三个文件的父路径是普通文件，写入失败：前两个文件都恢复原状
+        std::fs::write(workspace.join("blocker"), "x").unwrap();
+        let files = vec![
+            ("a.md".to_string(), "changed".to_string()),
+            ("new.md".to_string(), "new".to_string()),
+            ("blocker/c.md".to_string(), "c".to_string()),
+        ];
+        assert!(write_files_with_key(&workspace, &files, &key, false).await.is_err());
+        assert_eq!(read("a.md.enc"), "first");
+        assert!(!workspace.join("new.md.enc").exists());
+    }
+
+    #[tokio::test]
+    async fn test_copy_and_move_refuse_destination_inside_source() {
+        let workspace = temp_workspace("self_copy");
+        std::fs::create_dir_all(workspace.join("a/sub")).unwrap();
+        std::fs::write(workspace.join("a/sub/note.md.enc"), "x").unwrap();
+        let path = |p: &str| workspace.join(p).to_string_lossy().to_string();
+
+        for dest in ["a/backup", "a/sub/deeper/copy"] {
+            assert!(copy_file_or_directory(&path("a"), &path(dest)).await.is_err());
+            assert!(move_file_or_directory(&path("a"), &path(dest)).await.is_err());
+        }
+        assert!(!workspace.join("a/backup").exists());
+        assert!(!workspace.join("a/sub/deeper").exists());
+
+        copy_file_or_directory(&path("a"), &path("a-copy")).await.unwrap();
+        assert!(workspace.join("a-copy/sub/note.md.enc").exists());
+    }
+
+    #[tokio::test]
+    async fn test_copy_dir_limits_abort_and_clean_up() {
+        let workspace = temp_workspace("copy_limits");
+        let deep = workspace.join("src/1/2/3/4");
+        std::fs::create_dir_all(&deep).unwrap();
+        std::fs::write(deep.join("note.md.enc"), "x").unwrap();
+        let source = workspace.join("src");
+
+        let generous = CopyLimits { max_depth: 8, max_entries: 100 };
+        copy_dir_limited(&source, &workspace.join("ok"), generous).await.unwrap();
+        assert!(workspace.join("ok/1/2/3/4/note.md.enc").exists());
+
+        let shallow = CopyLimits { max_depth: 2, max_entries: 100 };
+        let err = copy_dir_limited(&source, &workspace.join("too_deep"), shallow).await.unwrap_err();
+        assert!(err.to_string().contains("层级"));
+        assert!(!workspace.join("too_deep").exists());
+
+        let few = CopyLimits { max_depth: 8, max_entries: 3 };
+        let err = copy_dir_limited(&source, &workspace.join("too_many"), few).await.unwrap_err();
+        assert!(err.to_string().contains("条目"));
+        assert!(!workspace.join("too_many").exists());
+    }
+
+    #[test]
+    fn test_can_decrypt_vault_samples_files() {
+        let workspace = temp_workspace("key_check");
+        let key = [7u8; 32];
+        assert!(can_decrypt_vault(&workspace, &[9u8; 32]));
+
+        for i in 0..20 {
+            write_note(&workspace, &format!("{:02}.md.enc", i), "x", &key);
+        }
+        assert!(can_decrypt_vault(&workspace, &key));
+        assert!(!can_decrypt_vault(&workspace, &[9u8; 32]));
+    }
+
+    #[tokio::test]
+    async fn test_vault_stats_counts_words_and_skips_foreign_files() {
+        let workspace = temp_workspace("stats");
+        std::fs::create_dir_all(workspace.join("notes")).unwrap();
+        let key = [7u8; 32];
+        let old_meta = NoteMeta {
+            created: "2024-01-01T00:00:00+00:00".to_string(),
+            modified: "2024-01-01T00:00:00+00:00".to_string(),
+        };
+        let new_meta = NoteMeta {
+            created: "2024-01-01T00:00:00+00:00".to_string(),
+            modified: "2025-06-01T00:00:00+00:00".to_string(),
+        };
+        std::fs::write(workspace.join("notes/a.md.enc"), encrypt_note("hello brave world", &old_meta, false, &key).unwrap()).unwrap();
+        std::fs::write(workspace.join("b.md.enc"), encrypt_note("今天 good", &new_meta, false, &key).unwrap()).unwrap();
+        std::fs::write(workspace.join("foreign.md.enc"), encrypt_note("x", &new_meta, false, &[9u8; 32]).unwrap()).unwrap();
+
+        let stats = collect_vault_stats(&workspace, &key).await.unwrap();
+        assert_eq!(stats.note_count, 2);
+        assert_eq!(stats.word_count, 6);
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.last_edited_path.as_deref(), Some("b.md"));
+        assert!(stats.total_size > 0);
+    }
+
+    #[test]
+    fn test_parse_front_matter_tags() {
+        assert_eq!(parse_front_matter_tags("---\ntitle: x\ntags: [rust, \"git\"]\n---\nbody"), vec!["rust", "git"]);
+        assert_eq!(parse_front_matter_tags("---\ntags: #daily, work\n---\n"), vec!["daily", "work"]);
+        assert_eq!(parse_front_matter_tags("---\ntags:\n  - 日记\n  - work\nother: 1\n---\n"), vec!["日记", "work"]);
+        assert!(parse_front_matter_tags("no front matter\ntags: [x]").is_empty());
+        assert!(parse_front_matter_tags("---\ntitle: x\n---\ntags: [x]").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_build_tag_index_maps_tags_to_notes() {
+        let workspace = temp_workspace("tags");
+        let key = [7u8; 32];
+        write_note(&workspace, "notes/a.md.enc", "---\ntags: [rust, git]\n---\n", &key);
+        write_note(&workspace, "b.md.enc", "---\ntags: [rust]\n---\n", &key);
+        write_note(&workspace, "c.md.enc", "plain", &key);
+
+        let index = build_tag_index(&workspace, &key).await;
+        assert_eq!(index.len(), 2);
+        assert_eq!(index["rust"], vec!["b.md.enc", "notes/a.md.enc"]);
+        assert_eq!(index["git"], vec!["notes/a.md.enc"]);
+    }
+
+    #[tokio::test]
+    async fn test_backlinks_resolve_by_name_and_path() {
+        let workspace = temp_workspace("backlinks");
+        let key = [7u8; 32];
+        write_note(&workspace, "notes/foo.md.enc", "see [[bar|the bar]] and [[Missing]] and [[foo]]", &key);
+        write_note(&workspace, "bar.md.enc", "back to [[notes/foo#intro]]\nand [[FOO]] again", &key);
+        write_note(&workspace, "index.md.enc", "[[foo]] [[bar.md]] [[broken\n]]", &key);
+
+        assert_eq!(extract_wiki_links("a [[x|y]] [[z#h]] [[]]"), vec!["x", "z"]);
+
+        let index = collect_backlinks(&workspace, &key).await;
+        assert_eq!(index.backlinks["notes/foo.md.enc"], vec!["bar.md.enc", "index.md.enc"]);
+        assert_eq!(index.backlinks["bar.md.enc"], vec!["index.md.enc", "notes/foo.md.enc"]);
+        assert_eq!(index.dangling["Missing"], vec!["notes/foo.md.enc"]);
+        assert!(!index.backlinks.contains_key("index.md.enc"));
+    }
+
+    #[tokio::test]
+    async fn test_rename_rewrites_inbound_links() {
+        let workspace = temp_workspace("relink");
+        let key = [7u8; 32];
+        write_note(&workspace, "notes/foo.md.enc", "I am foo", &key);
+        write_note(&workspace, "a.md.enc", "see [[foo]], [[notes/foo#intro]] and [[foo.md|alias]]", &key);
+        write_note(&workspace, "b.md.enc", "unrelated [[other]]", &key);
+
+        std::fs::rename(workspace.join("notes/foo.md.enc"), workspace.join("notes/bar.md.enc")).unwrap();
+        let updates = rewrite_links_after_rename(&workspace, "notes/foo.md.enc", "notes/bar.md.enc", &key).await;
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].0, workspace.join("a.md.enc"));
+        assert_eq!(updates[0].1, "see [[bar]], [[notes/bar#intro]] and [[bar.md|alias]]");
+
+        // 计算改写之后、写回之前被编辑过的笔记不能被旧内容覆盖
+        write_note(&workspace, "a.md.enc", "edited meanwhile [[foo]]", &key);
+        assert_eq!(apply_link_updates(&updates, &key, false).await.unwrap(), 0);
+        assert_eq!(decrypt_note(&std::fs::read(workspace.join("a.md.enc")).unwrap(), &key).unwrap().0, "edited meanwhile [[foo]]");
+        let updates = rewrite_links_after_rename(&workspace, "notes/foo.md.enc", "notes/bar.md.enc", &key).await;
+        assert_eq!(apply_link_updates(&updates, &key, false).await.unwrap(), 1);
+        assert_eq!(decrypt_note(&std::fs::read(workspace.join("a.md.enc")).unwrap(), &key).unwrap().0, "edited meanwhile [[bar]]");
+
+        assert_eq!(rewrite_wiki_links("no links", |_| Some("x".to_string())), None);
+    }
+
+    #[test]
+    fn test_atmosphere_config_is_inherited_from_parents() {
+        let outer = temp_workspace("vnode");
+        let workspace = outer.join("workspace");
+        std::fs::create_dir_all(workspace.join("a/b/c")).unwrap();
+        std::fs::write(outer.join(".vnode.json"), "{}").unwrap();
+
+        assert_eq!(find_atmosphere_config(&workspace, &workspace.join("a/b/c")), None);
+
+        std::fs::write(workspace.join("a/.vnode.json"), "{}").unwrap();
+        let found = find_atmosphere_config(&workspace, &workspace.join("a/b/c")).unwrap();
+        assert!(found.ends_with("a/.vnode.json"));
+
+        std::fs::write(workspace.join("a/b/.vnode.json"), "{}").unwrap();
+        let found = find_atmosphere_config(&workspace, &workspace.join("a/b/c")).unwrap();
+        assert!(found.ends_with("b/.vnode.json"));
+    }
+
+    #[test]
+    fn test_confine_to_workspace_rejects_traversal() {
+        let outer = temp_workspace("confine");
+        let workspace = outer.join("workspace");
+        std::fs::create_dir_all(workspace.join("notes")).unwrap();
+        std::fs::write(outer.join("secret.txt"), "x").unwrap();
+
+        assert!(confine_to_workspace(&workspace, &workspace.join("notes/a.md.enc")).is_ok());
+        assert!(confine_to_workspace(&workspace, &workspace.join("new_dir/b.md.enc")).is_ok());
+        assert!(confine_to_workspace(&workspace, Path::new("notes/c.md.enc")).is_ok());
+
+        assert!(confine_to_workspace(&workspace, &workspace.join("../secret.txt")).is_err());
+        assert!(confine_to_workspace(&workspace, &workspace.join("notes/../../secret.txt")).is_err());
+        assert!(confine_to_workspace(&workspace, Path::new("../../etc/passwd")).is_err());
+        assert!(confine_to_workspace(&workspace, Path::new("/etc/passwd")).is_err());
+        assert!(confine_to_workspace(&workspace, &workspace.join("missing/../../secret.txt")).is_err());
+    }
+
+    #[test]
+    fn test_rename_paths_in_list_updates_files_and_directories() {
+        let mut favorites = vec![
+            "notes/a.md.enc".to_string(),
+            "notes/sub/b.md.enc".to_string(),
+            "notes-old/c.md.enc".to_string(),
+            "d.md.enc".to_string(),
+        ];
+        assert!(rename_paths_in_list(&mut favorites, "notes", "archive/notes"));
+        assert_eq!(favorites, vec!["archive/notes/a.md.enc", "archive/notes/sub/b.md.enc", "notes-old/c.md.enc", "d.md.enc"]);
+
+        assert!(rename_paths_in_list(&mut favorites, "d.md.enc", "e.md.enc"));
+        assert_eq!(favorites[3], "e.md.enc");
+        assert!(!rename_paths_in_list(&mut favorites, "missing", "other"));
+    }
+
+    #[test]
+    fn test_render_template_fills_builtin_and_custom_variables() {
+        let mut variables = std::collections::HashMap::new();
+        variables.insert("project".to_string(), "Vana".to_string());
+        variables.insert("time".to_string(), "09:00".to_string());
+
+        let rendered = render_template("# {{title}}\n{{ date }} {{time}} {{project}} {{unknown}} {{open", "2026-10-16", &variables);
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(rendered, format!("# 2026-10-16\n{} 09:00 Vana {{{{unknown}}}} {{{{open", today));
+        assert_eq!(note_title(Path::new("/w/daily/2026-10-16.md.enc")), "2026-10-16");
+        assert_eq!(note_title(Path::new("/w/meeting.md")), "meeting");
+    }
+
+    #[tokio::test]
+    async fn test_delete_file_and_prune_removes_empty_parents() {
+        let workspace = temp_workspace("prune");
+        std::fs::create_dir_all(workspace.join("a/b/c")).unwrap();
+        std::fs::write(workspace.join("a/keep.md.enc"), "x").unwrap();
+        std::fs::write(workspace.join("a/b/c/only.md.enc"), "x").unwrap();
+
+        let pruned = delete_file_and_prune(workspace.join("a/b/c/only.md.enc").to_str().unwrap(), &workspace)
+            .await
+            .unwrap();
+        assert_eq!(pruned, 2);
+        assert!(!workspace.join("a/b").exists());
+        assert!(workspace.join("a/keep.md.enc").exists());
+
+        std::fs::remove_file(workspace.join("a/keep.md.enc")).unwrap();
+        std::fs::write(workspace.join("top.md.enc"), "x").unwrap();
+        let pruned = delete_file_and_prune(workspace.join("top.md.enc").to_str().unwrap(), &workspace)
+            .await
+            .unwrap();
+        assert_eq!(pruned, 0);
+        assert!(workspace.exists());
+    }
+
+    #[tokio::test]
+    async fn test_convert_plaintext_folder_notes_removes_leftover_copies() {
+        let _guard = PLAINTEXT_FOLDERS_TEST_LOCK.lock().await;
+        set_plaintext_folders(&["Public".to_string()]);
+        let workspace = temp_workspace("convert_plaintext");
+        let key = [7u8; 32];
+        write_note(&workspace, "Public/old.md.enc", "设为明文之前的笔记", &key);
+        write_note(&workspace, "Public/stale.md.enc", "过期副本", &key);
+        std::fs::write(workspace.join("Public/stale.md"), "当前内容").unwrap();
+        write_note(&workspace, "Public/foreign.md.enc", "x", &[9u8; 32]);
+        write_note(&workspace, "secret.md.enc", "secret", &key);
+
+        let converted = convert_plaintext_folder_notes(&workspace, &key).await;
+        set_plaintext_folders(&[]);
+        assert_eq!(converted.unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(workspace.join("Public/old.md")).unwrap(), "设为明文之前的笔记");
+        assert!(!workspace.join("Public/old.md.enc").exists());
+        assert_eq!(std::fs::read_to_string(workspace.join("Public/stale.md")).unwrap(), "当前内容");
+        assert!(!workspace.join("Public/stale.md.enc").exists());
+        assert!(workspace.join("Public/foreign.md.enc").exists(), "无法解密的文件应保留");
+        assert!(workspace.join("secret.md.enc").exists());
+    }
+
+    #[tokio::test]
+    async fn test_move_note_reencrypted_switches_key_and_keeps_meta() {
+        let root = temp_workspace("move_vault");
+        std::fs::create_dir_all(root.join("work")).unwrap();
+        let (work_key, personal_key) = ([1u8; 32], [2u8; 32]);
+        let meta = NoteMeta { created: "2020-01-01T00:00:00+00:00".to_string(), modified: "2021-01-01T00:00:00+00:00".to_string() };
+        let source = root.join("work/plan.md.enc");
+        std::fs::write(&source, encrypt_note("secret plan", &meta, false, &work_key).unwrap()).unwrap();
+        let dest = root.join("personal/notes/plan.md.enc");
+
+        move_note_reencrypted(&source, &work_key, &dest, &personal_key).await.unwrap();
+        assert!(!source.exists());
+        let (content, moved_meta) = decrypt_note(&std::fs::read(&dest).unwrap(), &personal_key).unwrap();
+        assert_eq!(content, "secret plan");
+        assert_eq!(moved_meta.unwrap().created, meta.created);
+        assert!(decrypt_note(&std::fs::read(&dest).unwrap(), &work_key).is_err());
+
+        // 目标已存在时不覆盖，源文件保留
+        std::fs::write(&source, encrypt_note("other", &meta, false, &work_key).unwrap()).unwrap();
+        assert!(move_note_reencrypted(&source, &work_key, &dest, &personal_key).await.is_err());
+        assert!(source.exists());
+
+        // 明文目录中的笔记：原始文件移入加密位置，加密笔记移入明文目录
+        let public = root.join("work/Public/readme.md");
+        std::fs::create_dir_all(public.parent().unwrap()).unwrap();
+        std::fs::write(&public, "公开笔记").unwrap();
+        let encrypted_dest = root.join("personal/readme.md.enc");
+        move_note_reencrypted(&public, &work_key, &encrypted_dest, &personal_key).await.unwrap();
+        assert!(!public.exists());
+        assert_eq!(decrypt_note(&std::fs::read(&encrypted_dest).unwrap(), &personal_key).unwrap().0, "公开笔记");
+
+        let plain_dest = root.join("personal/Public/plan.md");
+        move_note_reencrypted(&source, &work_key, &plain_dest, &personal_key).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&plain_dest).unwrap(), "other");
+        // 目标的加密版本已存在时同样拒绝
+        std::fs::write(root.join("personal/Public/other.md.enc"), "x").unwrap();
+        std::fs::write(&public, "again").unwrap();
+        assert!(move_note_reencrypted(&public, &work_key, &root.join("personal/Public/other.md"), &personal_key).await.is_err());
+        assert!(public.exists());
+    }
+
+    #[test]
+    fn test_vault_plaintext_folders_reads_other_vault_config() {
+        let vault = temp_workspace("vault_plaintext_folders");
+        let key = [3u8; 32];
+        assert!(vault_plaintext_folders(&vault, &key).is_empty());
+
+        std::fs::create_dir_all(vault.join(".config")).unwrap();
+        let config = crate::commands::WorkspaceConfig {
+            plaintext_folders: vec![" Public/ ".to_string()],
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        std::fs::write(vault.join(".config/settings.json"), crate::crypto::encrypt_content(&json, &key).unwrap()).unwrap();
+        assert_eq!(vault_plaintext_folders(&vault, &key), vec!["Public"]);
+        assert!(vault_plaintext_folders(&vault, &[4u8; 32]).is_empty());
+
+        std::fs::write(vault.join(".config/settings.json"), &json).unwrap();
+        let (plain, encrypted, in_plaintext) = vault_note_paths(&vault, "Public/a.md.enc", &["Public".to_string()]).unwrap();
+        assert!(in_plaintext);
+        assert!(plain.ends_with("Public/a.md"));
+        assert!(encrypted.ends_with("Public/a.md.enc"));
+        assert!(!vault_note_paths(&vault, "notes/a.md", &["Public".to_string()]).unwrap().2);
+        assert!(vault_note_paths(&vault, "../a.md", &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rekey_note_upgrades_legacy_format_in_place() {
+        let workspace = temp_workspace("rekey");
+        let key = [7u8; 32];
+        write_note(&workspace, "legacy.md.enc", "old note", &key);
+        let file = workspace.join("legacy.md.enc");
+        assert!(decrypt_note(&std::fs::read(&file).unwrap(), &key).unwrap().1.is_none());
+
+        rekey_note(&file, &key, false).await.unwrap();
+        let (content, meta) = decrypt_note(&std::fs::read(&file).unwrap(), &key).unwrap();
+        assert_eq!(content, "old note");
+        assert!(meta.is_some());
+        assert_eq!(std::fs::read_dir(&*workspace).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_available_space_for_missing_path_uses_existing_parent() {
+        let dir = temp_workspace("available_space");
+        assert!(get_available_space(&dir.join("missing_dir/nested")).is_ok());
+        assert!(ensure_space_available(&dir, 0).is_ok());
+        assert!(ensure_space_available(&dir, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_preview_text_counts_characters() {
+        assert_eq!(preview_text("hello", 10), "hello");
+        assert_eq!(preview_text("hello", 2), "he");
+        assert_eq!(preview_text("你好世界", 2), "你好");
+        assert_eq!(preview_text("abc", 0), "");
+    }
+}
@@ -0,0 +1,56 @@
+// No Visitors - 测试辅助模块
+// 各模块测试共用的临时工作区与加密笔记夹具，只在测试构建中编译。
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// 同一进程内的目录序号：不同模块的测试可以使用相同的名称而不会互相覆盖
+static NEXT_DIR_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// 测试用的临时工作区，离开作用域时删除（断言失败 panic 时同样会清理）
+pub(crate) struct TempWorkspace(PathBuf);
+
+impl TempWorkspace {
+    /// 取出路径，不再自动删除（由调用方自行清理，例如需要跨线程传递路径的测试）
+    pub(crate) fn into_path(mut self) -> PathBuf {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl std::ops::Deref for TempWorkspace {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempWorkspace {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempWorkspace {
+    fn drop(&mut self) {
+        if !self.0.as_os_str().is_empty() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
+/// 在系统临时目录下创建一个空的测试目录
+pub(crate) fn temp_workspace(name: &str) -> TempWorkspace {
+    let id = NEXT_DIR_ID.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("vana_test_{}_{}_{}", name, std::process::id(), id));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    TempWorkspace(path)
+}
+
+/// 用 `key` 加密写入一篇笔记（自动创建上级目录）
+pub(crate) fn write_note(workspace: &Path, path: &str, text: &str, key: &[u8]) {
+    let target = workspace.join(path);
+    std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+    std::fs::write(target, crate::crypto::encrypt_content(text, key).unwrap()).unwrap();
+}
@@ -0,0 +1,199 @@
+// No Visitors - 多工作区（vault）模块
+// 维护命名的工作区列表和当前激活的工作区。注册表保存在应用存储的 vaults.json 中（不在任何工作区内），
+// get_workspace_path 返回激活工作区的路径，因此所有以工作区路径为参数的命令都作用于激活的工作区。
+
+use anyhow::{Context, Result};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+/// 默认工作区（升级前唯一的工作区）的 ID
+pub const DEFAULT_VAULT_ID: &str = "default";
+
+const VAULTS_STORE_FILE: &str = "vaults.json";
+const VAULT_REGISTRY_STORE_KEY: &str = "vault_registry";
+
+/// 已注册的工作区
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultEntry {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+}
+
+/// 工作区注册表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultRegistry {
+    pub vaults: Vec<VaultEntry>,
+    pub active_id: String,
+}
+
+impl VaultRegistry {
+    /// 只包含默认工作区的注册表
+    fn with_default(default_path: &str) -> Self {
+        VaultRegistry {
+            vaults: vec![VaultEntry {
+                id: DEFAULT_VAULT_ID.to_string(),
+                name: "默认".to_string(),
+                path: default_path.to_string(),
+            }],
+            active_id: DEFAULT_VAULT_ID.to_string(),
+        }
+    }
+
+    /// 当前激活的工作区（激活 ID 失效时回退到第一个工作区）
+    pub fn active(&self) -> Option<&VaultEntry> {
+        self.vaults
+            .iter()
+            .find(|v| v.id == self.active_id)
+            .or_else(|| self.vaults.first())
+    }
+
+    /// 注册新的工作区（名称不能为空，路径必须为绝对路径且不能重复注册）
+    fn add(&mut self, name: &str, path: &str) -> Result<VaultEntry> {
+        let name = name.trim();
+        if name.is_empty() {
+            anyhow::bail!("工作区名称不能为空");
+        }
+        if !Path::new(path).is_absolute() {
+            anyhow::bail!("工作区路径必须是绝对路径: {}", path);
+        }
+        if self.vaults.iter().any(|v| Path::new(&v.path) == Path::new(path)) {
+            anyhow::bail!("该路径已注册为工作区: {}", path);
+        }
+
+        let mut id_bytes = [0u8; 4];
+        rand::thread_rng().fill_bytes(&mut id_bytes);
+        let id = format!("vault_{}", id_bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>());
+
+        let entry = VaultEntry {
+            id,
+            name: name.to_string(),
+            path: path.to_string(),
+        };
+        self.vaults.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// 切换激活的工作区
+    fn switch(&mut self, id: &str) -> Result<VaultEntry> {
+        let entry = self
+            .vaults
+            .iter()
+            .find(|v| v.id == id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("工作区不存在: {}", id))?;
+        self.active_id = entry.id.clone();
+        Ok(entry)
+    }
+}
+
+/// 读取注册表；尚未保存过时返回只包含默认工作区的注册表
+fn load_registry(app: &AppHandle) -> Result<VaultRegistry> {
+    use tauri_plugin_store::StoreBuilder;
+
+    let store = StoreBuilder::new(app, PathBuf::from(VAULTS_STORE_FILE)).build()?;
+    let registry = store
+        .get(VAULT_REGISTRY_STORE_KEY)
+        .and_then(|value| serde_json::from_value::<VaultRegistry>(value).ok())
+        .filter(|registry| !registry.vaults.is_empty());
+
+    match registry {
+        Some(registry) => Ok(registry),
+        None => {
+            let default_path = crate::commands::default_workspace_path(app)
+                .map_err(|e| anyhow::anyhow!(e))?;
+            Ok(VaultRegistry::with_default(&default_path))
+        }
+    }
+}
+
+fn save_registry(app: &AppHandle, registry: &VaultRegistry) -> Result<()> {
+    use tauri_plugin_store::StoreBuilder;
+
+    let store = StoreBuilder::new(app, PathBuf::from(VAULTS_STORE_FILE)).build()?;
+    store.set(
+        VAULT_REGISTRY_STORE_KEY.to_string(),
+        serde_json::to_value(registry).context("无法序列化工作区注册表")?,
+    );
+    store.save()?;
+    Ok(())
+}
+
+/// 列出所有工作区
+pub fn list_vaults(app: &AppHandle) -> Result<VaultRegistry> {
+    load_registry(app)
+}
+
+/// 获取当前激活的工作区
+pub fn active_vault(app: &AppHandle) -> Result<VaultEntry> {
+    load_registry(app)?
+        .active()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("没有可用的工作区"))
+}
+
+/// 按 ID 查找已注册的工作区
+pub fn find_vault(app: &AppHandle, id: &str) -> Result<VaultEntry> {
+    load_registry(app)?
+        .vaults
+        .into_iter()
+        .find(|v| v.id == id)
+        .ok_or_else(|| anyhow::anyhow!("工作区不存在: {}", id))
+}
+
+/// 注册新的工作区（目录不存在时创建；Git 仓库在切换后由 ensure_workspace_initialized 初始化）
+///
+/// # 参数
+/// - `name`: 显示名称
+/// - `path`: 工作区目录的绝对路径
+pub fn add_vault(app: &AppHandle, name: &str, path: &str) -> Result<VaultEntry> {
+    let mut registry = load_registry(app)?;
+    let entry = registry.add(name, path)?;
+    std::fs::create_dir_all(path)
+        .with_context(|| format!("无法创建工作区目录: {}", path))?;
+    save_registry(app, &registry)?;
+    tracing::info!("[Vaults] 已注册工作区 {} ({}): {}", entry.name, entry.id, entry.path);
+    Ok(entry)
+}
+
+/// 切换激活的工作区
+///
+/// # 参数
+/// - `id`: 工作区 ID
+pub fn switch_vault(app: &AppHandle, id: &str) -> Result<VaultEntry> {
+    let mut registry = load_registry(app)?;
+    let entry = registry.switch(id)?;
+    save_registry(app, &registry)?;
+    // 每个工作区使用独立的主密钥
+    crate::keychain::invalidate_master_key_cache();
+    tracing::info!("[Vaults] 已切换到工作区 {} ({})", entry.name, entry.id);
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_add_and_switch() {
+        let mut registry = VaultRegistry::with_default("/data/workspace");
+        assert_eq!(registry.active().unwrap().id, DEFAULT_VAULT_ID);
+
+        let work = registry.add("Work", "/data/work").unwrap();
+        assert!(work.id.starts_with("vault_"));
+        assert!(registry.add("Again", "/data/work").is_err());
+        assert!(registry.add("Relative", "work").is_err());
+        assert!(registry.add("  ", "/data/other").is_err());
+
+        registry.switch(&work.id).unwrap();
+        assert_eq!(registry.active().unwrap().path, "/data/work");
+        assert!(registry.switch("missing").is_err());
+        assert_eq!(registry.active().unwrap().id, work.id);
+
+        // 激活 ID 失效时回退到第一个工作区
+        registry.active_id = "gone".to_string();
+        assert_eq!(registry.active().unwrap().id, DEFAULT_VAULT_ID);
+    }
+}
@@ -0,0 +1,135 @@
+// No Visitors - 工作区文件监听模块
+// 同步、checkout 或其他设备通过共享目录写入时，工作区文件会在应用之外发生变化。
+// 这里监听工作区目录，对文件系统事件去抖后通知前端刷新（可选自动提交），不再需要手动刷新。
+
+use crate::events;
+use anyhow::{Context, Result};
+use notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::AppHandle;
+
+/// 默认去抖间隔：同步/checkout 会在短时间内写入大量文件，合并为一次通知
+pub const DEFAULT_WATCH_DEBOUNCE_MS: u64 = 500;
+
+// 当前的监听器（同一时间只监听一个工作区；丢弃即停止监听）
+static WATCHER: Mutex<Option<Debouncer<notify::RecommendedWatcher>>> = Mutex::new(None);
+
+/// 从事件路径中筛选出需要通知前端的工作区文件（相对路径，`/` 分隔，去重排序）
+///
+/// `.git` 等隐藏目录下的变化（包括本应用自己的提交）会被忽略
+fn relevant_changes(workspace: &Path, paths: &[PathBuf]) -> Vec<String> {
+    let mut changed: Vec<String> = paths
+        .iter()
+        .filter_map(|path| path.strip_prefix(workspace).ok())
+        .filter(|relative| {
+            !relative.as_os_str().is_empty()
+                && relative.components().all(|c| match c {
+                    Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+                    _ => false,
+                })
+        })
+        .map(|relative| {
+            relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .collect();
+    changed.sort();
+    changed.dedup();
+    changed
+}
+
+/// 开始监听工作区（已有监听器时先停止，再监听新的目录）
+///
+/// # 参数
+/// - `app`: Tauri 应用句柄，用于发送 `workspace:changed` 事件
+/// - `workspace_path`: 工作区根目录
+/// - `debounce_ms`: 去抖间隔（毫秒）
+/// - `auto_commit`: 检测到变化后是否按工作区配置自动提交
+pub fn start_watching(app: AppHandle, workspace_path: &str, debounce_ms: u64, auto_commit: bool) -> Result<()> {
+    let workspace = PathBuf::from(workspace_path);
+    // 事件路径是规范化后的绝对路径，工作区路径也需要规范化才能正确截取相对路径
+    let workspace_root = workspace
+        .canonicalize()
+        .with_context(|| format!("工作区不存在: {}", workspace_path))?;
+
+    let handler_root = workspace_root.clone();
+    let mut debouncer = new_debouncer(Duration::from_millis(debounce_ms.max(1)), move |result: DebounceEventResult| {
+        let debounced = match result {
+            Ok(debounced) => debounced,
+            Err(e) => {
+                tracing::warn!("[Watcher] 监听出错: {}", e);
+                return;
+            }
+        };
+        let paths: Vec<PathBuf> = debounced.into_iter().map(|event| event.path).collect();
+        let changed = relevant_changes(&handler_root, &paths);
+        if changed.is_empty() {
+            return;
+        }
+
+        tracing::info!("[Watcher] 检测到 {} 个文件变化", changed.len());
+        events::emit_workspace_changed(&app, changed);
+        if auto_commit {
+            let app = app.clone();
+            let repo_path = handler_root.to_string_lossy().to_string();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = crate::commands::auto_commit_command(repo_path, app).await {
+                    tracing::warn!("[Watcher] 自动提交失败: {}", e);
+                }
+            });
+        }
+    })
+    .context("无法创建文件监听器")?;
+
+    debouncer
+        .watcher()
+        .watch(&workspace_root, RecursiveMode::Recursive)
+        .with_context(|| format!("无法监听工作区: {}", workspace_root.display()))?;
+
+    let mut current = WATCHER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    *current = Some(debouncer);
+    tracing::info!("[Watcher] 开始监听工作区: {}", workspace_root.display());
+    Ok(())
+}
+
+/// 停止监听工作区
+///
+/// # 返回
+/// 之前有监听器在运行时返回 true
+pub fn stop_watching() -> bool {
+    let stopped = WATCHER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+        .is_some();
+    if stopped {
+        tracing::info!("[Watcher] 已停止监听工作区");
+    }
+    stopped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relevant_changes_skips_hidden_and_outside_paths() {
+        let workspace = Path::new("/data/workspace");
+        let paths = vec![
+            workspace.join("notes/a.md.enc"),
+            workspace.join("notes/a.md.enc"),
+            workspace.join(".git/index"),
+            workspace.join("notes/.vnode.json"),
+            workspace.join("b.md.enc"),
+            workspace.to_path_buf(),
+            PathBuf::from("/elsewhere/c.md.enc"),
+        ];
+        assert_eq!(relevant_changes(workspace, &paths), vec!["b.md.enc", "notes/a.md.enc"]);
+    }
+}
@@ -439,7 +439,7 @@ export interface SyncConflict {
   files: SyncConflictFile[];
 }
 
-export type ConflictResolutionChoice = 'Ours' | 'Theirs' | 'CopyBoth';
+export type ConflictResolutionChoice = 'Ours' | 'Theirs' | 'CopyBoth' | 'Merge';
 
 export interface ConflictResolutionItem {
   path: string;
@@ -553,12 +553,13 @@ export async function abortSync(path: string): Promise<void> {
 
 /**
  * 解决冲突（写入工作区 + stage），随后应调用 continueSync
+ * @returns 选择 Merge 后仍带冲突标记、需要用户编辑的文件
  */
 export async function resolveConflict(
   path: string,
   items: ConflictResolutionItem[]
-): Promise<void> {
-  return await invoke<void>('resolve_conflict', { path, items });
+): Promise<string[]> {
+  return await invoke<string[]>('resolve_conflict', { path, items });
 }
 
 /**